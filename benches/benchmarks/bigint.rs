@@ -76,6 +76,10 @@ fn multiply_3(c: &mut Criterion) {
     multiply_bench(c, "multiply_3".to_string(), 1 << 16, 1 << 17);
 }
 
+fn multiply_4(c: &mut Criterion) {
+    multiply_bench(c, "multiply_4".to_string(), 1 << 14, 1 << 20);
+}
+
 fn divide_0(c: &mut Criterion) {
     divide_bench(c, "divide_0".to_string(), 1 << 8, 1 << 6);
 }
@@ -88,6 +92,10 @@ fn divide_2(c: &mut Criterion) {
     divide_bench(c, "divide_2".to_string(), 1 << 16, 1 << 12);
 }
 
+fn divide_3(c: &mut Criterion) {
+    divide_bench(c, "divide_3".to_string(), 1 << 18, 1 << 16);
+}
+
 fn factorial_100(c: &mut Criterion) {
     c.bench_function("factorial_100", move |b| b.iter(|| factorial(100)));
 }
@@ -185,6 +193,20 @@ fn from_str_radix_36(c: &mut Criterion) {
     from_str_radix_bench(c, 36);
 }
 
+fn big_to_string(c: &mut Criterion) {
+    let mut rng = get_rng();
+    let x = rng.gen_bigint(1 << 18);
+    c.bench_function("big_to_string", move |b| b.iter(|| x.to_string()));
+}
+
+fn big_from_str(c: &mut Criterion) {
+    let mut rng = get_rng();
+    let s = rng.gen_bigint(1 << 18).to_string();
+    c.bench_function("big_from_str", move |b| {
+        b.iter(|| BigInt::from_str_radix(&s, 10))
+    });
+}
+
 fn rand_bench(c: &mut Criterion, bits: usize) {
     let mut rng = get_rng();
     c.bench_function(&format!("rand_bench_{:?}", bits), move |b| {
@@ -339,9 +361,11 @@ criterion_group! {
         multiply_1,
         multiply_2,
         multiply_3,
+        multiply_4,
         divide_0,
         divide_1,
         divide_2,
+        divide_3,
         factorial_100,
         fib_100,
         fib_1000,
@@ -361,6 +385,8 @@ criterion_group! {
         from_str_radix_10,
         from_str_radix_16,
         from_str_radix_36,
+        big_to_string,
+        big_from_str,
         rand_64,
         rand_256,
         rand_1009,
@@ -1,9 +1,10 @@
+use integer::Integer;
 use num_traits::{One, Zero};
 use smallvec::SmallVec;
 use std::cmp::Ordering;
 
-use crate::algorithms::{add2, cmp_slice, sub2};
-use crate::big_digit::{self, BigDigit, DoubleBigDigit};
+use crate::algorithms::{adc, add2, burnikel_ziegler_threshold, cmp_slice, sbb, sub2};
+use crate::big_digit::{self, BigDigit, DoubleBigDigit, SignedDoubleBigDigit};
 use crate::BigUint;
 
 pub fn div_rem_digit(mut a: BigUint, b: BigDigit) -> (BigUint, BigDigit) {
@@ -56,16 +57,31 @@ pub fn div_rem(u: &BigUint, d: &BigUint) -> (BigUint, BigUint) {
         Ordering::Greater => {} // Do nothing
     }
 
-    // This algorithm is from Knuth, TAOCP vol 2 section 4.3, algorithm D:
-    //
     // First, normalize the arguments so the highest bit in the highest digit of the divisor is
-    // set: the main loop uses the highest digit of the divisor for generating guesses, so we
-    // want it to be the largest number we can efficiently divide by.
-    //
+    // set: both algorithms below rely on it to bound the error of their quotient guesses.
     let shift = d.data.last().unwrap().leading_zeros() as usize;
-    let mut a = u << shift;
+    let a = u << shift;
     let b = d << shift;
 
+    let threshold = burnikel_ziegler_threshold();
+    let (q, r) = if b.data.len() >= threshold && a.data.len() - b.data.len() >= threshold {
+        div_rem_burnikel_ziegler(a, &b)
+    } else {
+        div_rem_knuth(a, &b)
+    };
+
+    (q, r >> shift)
+}
+
+/// Schoolbook division of `a` by the normalized divisor `b` (the highest bit of its highest
+/// digit must be set).
+fn div_rem_knuth(mut a: BigUint, b: &BigUint) -> (BigUint, BigUint) {
+    if a < *b {
+        return (Zero::zero(), a);
+    }
+
+    // This algorithm is from Knuth, TAOCP vol 2 section 4.3, algorithm D.
+
     // The algorithm works by incrementally calculating "guesses", q0, for part of the
     // remainder. Once we have any number q0 such that q0 * b <= a, we can set
     //
@@ -118,12 +134,12 @@ pub fn div_rem(u: &BigUint, d: &BigUint) -> (BigUint, BigUint) {
          * smaller numbers.
          */
         let (mut q0, _) = div_rem_digit(a0, bn);
-        let mut prod = &b * &q0;
+        let mut prod = b * &q0;
 
         while cmp_slice(&prod.data[..], &a.data[j..]) == Ordering::Greater {
             let one: BigUint = One::one();
             q0 = q0 - one;
-            prod = prod - &b;
+            prod = prod - b;
         }
 
         add2(&mut q.data[j..], &q0.data[..]);
@@ -133,7 +149,259 @@ pub fn div_rem(u: &BigUint, d: &BigUint) -> (BigUint, BigUint) {
         tmp = q0;
     }
 
-    debug_assert!(a < b);
+    debug_assert!(a < *b);
+
+    (q.normalized(), a)
+}
+
+/// Like `div_rem`, but writes the quotient and remainder into `q` and `r`, and keeps all
+/// temporary values in `scratch`. Once `q`, `r` and `scratch` have grown large enough, this
+/// doesn't allocate, unless the division is large enough for Burnikel-Ziegler division.
+pub(crate) fn div_rem_scratch(
+    q: &mut BigUint,
+    r: &mut BigUint,
+    u: &BigUint,
+    d: &BigUint,
+    scratch: &mut Vec<BigDigit>,
+) {
+    if d.is_zero() {
+        panic!()
+    }
+
+    q.data.clear();
+    r.data.clear();
+
+    if u < d {
+        r.data.extend_from_slice(&u.data);
+        return;
+    }
+
+    let n = d.data.len();
+    if n == 1 {
+        q.data.resize(u.data.len(), 0);
+        let mut rem = 0;
+        for (qi, &ui) in q.data.iter_mut().zip(u.data.iter()).rev() {
+            let (digit, rest) = div_wide(rem, ui, d.data[0]);
+            *qi = digit;
+            rem = rest;
+        }
+        q.normalize();
+        if rem != 0 {
+            r.data.push(rem);
+        }
+        return;
+    }
+
+    let threshold = burnikel_ziegler_threshold();
+    if n >= threshold && u.data.len() - n >= threshold {
+        let (div, rem) = div_rem(u, d);
+        q.data.extend_from_slice(&div.data);
+        r.data.extend_from_slice(&rem.data);
+        return;
+    }
+
+    // Normalize like `div_rem`, into a (with room for the bits shifted out at the top) and b.
+    let shift = d.data[n - 1].leading_zeros() as usize;
+    scratch.clear();
+    scratch.resize(u.data.len() + 1 + n, 0);
+    let (a, b) = scratch.split_at_mut(u.data.len() + 1);
+    shl_bits(a, &u.data, shift);
+    shl_bits(b, &d.data, shift);
+
+    q.data.resize(u.data.len() - n + 1, 0);
+    div_rem_knuth_slices(&mut q.data, a, b);
+    q.normalize();
+
+    r.data.extend_from_slice(&a[..n]);
+    if shift > 0 {
+        let mut carry = 0;
+        for ri in r.data.iter_mut().rev() {
+            let digit = *ri;
+            *ri = (digit >> shift) | carry;
+            carry = digit << (big_digit::BITS - shift);
+        }
+    }
+    r.normalize();
+}
+
+/// Sets `a` to the digits of `u` shifted left by `shift < big_digit::BITS` bits, including the
+/// bits shifted out at the top if `a` is one digit longer than `u`.
+fn shl_bits(a: &mut [BigDigit], u: &[BigDigit], shift: usize) {
+    let mut carry = 0;
+    for (ai, &ui) in a.iter_mut().zip(u.iter()) {
+        *ai = (ui << shift) | carry;
+        carry = if shift > 0 {
+            ui >> (big_digit::BITS - shift)
+        } else {
+            0
+        };
+    }
+    if a.len() > u.len() {
+        a[u.len()] = carry;
+    }
+}
+
+/// Schoolbook division (Knuth, TAOCP vol 2 section 4.3, algorithm D) of `a` by the normalized
+/// divisor `b`, with at least two digits, entirely in place: the quotient is written to `q`,
+/// which has `a.len() - b.len()` digits, and the remainder is left in the low digits of `a`.
+///
+/// Unlike `div_rem_knuth`, each quotient digit is estimated from the top three digits of the
+/// remainder and the top two digits of the divisor, which makes it at most one too large.
+fn div_rem_knuth_slices(q: &mut [BigDigit], a: &mut [BigDigit], b: &[BigDigit]) {
+    let n = b.len();
+    let (b1, b0) = (b[n - 1] as DoubleBigDigit, b[n - 2] as DoubleBigDigit);
+
+    for j in (0..q.len()).rev() {
+        // The remainder so far is less than b * B^(j + 1), so a[j + n] <= b1 and the estimate
+        // is at most B + 1 before the correction.
+        let num = big_digit::to_doublebigdigit(a[j + n], a[j + n - 1]);
+        let mut qhat = num / b1;
+        let mut rhat = num % b1;
+        while qhat >> big_digit::BITS != 0
+            || qhat * b0 > (rhat << big_digit::BITS) | a[j + n - 2] as DoubleBigDigit
+        {
+            qhat -= 1;
+            rhat += b1;
+            if rhat >> big_digit::BITS != 0 {
+                break;
+            }
+        }
+
+        // a[j..] -= qhat * b
+        let mut carry: DoubleBigDigit = 0;
+        let mut borrow: SignedDoubleBigDigit = 0;
+        for (ai, &bi) in a[j..j + n].iter_mut().zip(b.iter()) {
+            carry += qhat * bi as DoubleBigDigit;
+            *ai = sbb(*ai, carry as BigDigit, &mut borrow);
+            carry >>= big_digit::BITS;
+        }
+        a[j + n] = sbb(a[j + n], carry as BigDigit, &mut borrow);
+
+        if borrow < 0 {
+            // qhat was one too large, add b back (the carry out cancels the borrow)
+            qhat -= 1;
+            let mut carry: DoubleBigDigit = 0;
+            for (ai, &bi) in a[j..j + n].iter_mut().zip(b.iter()) {
+                *ai = adc(*ai, bi, &mut carry);
+            }
+            a[j + n] = adc(a[j + n], 0, &mut carry);
+        }
+
+        q[j] = qhat as BigDigit;
+    }
+}
+
+/// Recursive division of `a` by the normalized divisor `b`, from C. Burnikel and J. Ziegler,
+/// "Fast Recursive Division", MPI-I-98-1-022.
+///
+/// `a` is split into chunks of `n = len(b)` digits, and each two-chunk division is done by
+/// `div_2n_1n`, which reduces it to two divisions of half the size plus a few multiplications.
+/// Those multiplications go through `mac3`, so for large operands this is as fast as
+/// Karatsuba/Toom-3 multiplication up to a logarithmic factor, instead of quadratic.
+fn div_rem_burnikel_ziegler(a: BigUint, b: &BigUint) -> (BigUint, BigUint) {
+    let n = b.data.len();
+
+    let chunks = Integer::div_ceil(&a.data.len(), &n);
+    let mut q = BigUint {
+        data: smallvec![0; chunks * n],
+    };
+    let mut r = BigUint::zero();
+
+    for i in (0..chunks).rev() {
+        let lo = i * n;
+        let hi = ::std::cmp::min(lo + n, a.data.len());
+
+        // r < b, so r * B^n + chunk < b * B^n and the quotient digit fits into n digits.
+        let mut x = shl_digits(&r, n);
+        add2(&mut x.data[..], &a.data[lo..hi]);
+        x.normalize();
+
+        let (qi, ri) = div_2n_1n(x, b, n);
+        q.data[lo..lo + qi.data.len()].copy_from_slice(&qi.data);
+        r = ri;
+    }
+
+    (q.normalized(), r)
+}
+
+/// Divides `a < b * B^n` by the normalized `n`-digit divisor `b`.
+fn div_2n_1n(a: BigUint, b: &BigUint, n: usize) -> (BigUint, BigUint) {
+    if n < burnikel_ziegler_threshold() {
+        return div_rem_knuth(a, b);
+    }
+
+    // Split in halves of equal length; for odd n, scale both operands by one digit first.
+    if n % 2 == 1 {
+        let (q, r) = div_2n_1n(shl_digits(&a, 1), &shl_digits(b, 1), n + 1);
+        return (q, shr_digits(&r, 1));
+    }
+    let half = n / 2;
+
+    let (b1, b2) = split_digits(b, half);
+    let (a12, a3) = split_digits(&a, half);
+    let (a1, a2) = split_digits(&a12, half);
+
+    let (q1, r) = div_3n_2n(a1, a2, b, &b1, &b2, half);
+    let (q2, r) = div_3n_2n(r, a3, b, &b1, &b2, half);
+
+    let mut q = shl_digits(&q1, half);
+    add2(&mut q.data[..], &q2.data);
+    (q.normalized(), r)
+}
+
+/// Divides `a1 * B^2n + a2 * B^n + a3 < b * B^n` by the normalized `2n`-digit divisor
+/// `b = b1 * B^n + b2`, where `a2` and `a3` have at most `n` digits.
+fn div_3n_2n(
+    a12: BigUint,
+    a3: BigUint,
+    b: &BigUint,
+    b1: &BigUint,
+    b2: &BigUint,
+    n: usize,
+) -> (BigUint, BigUint) {
+    // Estimate the quotient from the top digits. If they are equal, the quotient is at
+    // least B^n - 1, but it also fits into n digits, so that is our guess.
+    let (mut q, r1) = if a12.data.len() > n && shr_digits(&a12, n) == *b1 {
+        let q = BigUint {
+            data: smallvec![!0; n],
+        };
+        let r1 = a12 - shl_digits(b1, n) + b1;
+        (q, r1)
+    } else {
+        div_2n_1n(a12, b1, n)
+    };
+
+    // Fix up the remainder; since b is normalized the guess is at most two too large.
+    let mut r = shl_digits(&r1, n);
+    add2(&mut r.data[..], &a3.data);
+    r.normalize();
+
+    let d = &q * b2;
+    while r < d {
+        q -= 1u32;
+        r += b;
+    }
+    (q, r - d)
+}
+
+/// Returns `x * B^n`, with room for `n` more digits than `x` (even if `x` is zero).
+fn shl_digits(x: &BigUint, n: usize) -> BigUint {
+    let mut data = SmallVec::with_capacity(x.data.len() + n);
+    data.resize(n, 0);
+    data.extend(x.data.iter().cloned());
+    BigUint { data }
+}
+
+/// Returns `x / B^n`.
+fn shr_digits(x: &BigUint, n: usize) -> BigUint {
+    if x.data.len() <= n {
+        return Zero::zero();
+    }
+    BigUint::from_slice_native(&x.data[n..])
+}
 
-    (q.normalized(), a >> shift)
+/// Returns `(x / B^n, x % B^n)`.
+fn split_digits(x: &BigUint, n: usize) -> (BigUint, BigUint) {
+    let lo = ::std::cmp::min(n, x.data.len());
+    (shr_digits(x, n), BigUint::from_slice_native(&x.data[..lo]))
 }
@@ -1,10 +1,16 @@
-use crate::big_digit::{BigDigit, DoubleBigDigit, BITS};
+use crate::algorithms::{
+    adc, extended_half_gcd_threshold, half_gcd_threshold, mac_with_carry, sbb,
+};
+use crate::big_digit::{BigDigit, DoubleBigDigit, SignedDoubleBigDigit, BITS};
 use crate::bigint::Sign::*;
 use crate::bigint::{BigInt, ToBigInt};
 use crate::biguint::{BigUint, IntDigits};
 use integer::Integer;
 use num_traits::{One, Signed, Zero};
+use smallvec::SmallVec;
 use std::borrow::Cow;
+use std::cmp;
+use std::mem;
 use std::ops::Neg;
 
 /// XGCD sets z to the greatest common divisor of a and b and returns z.
@@ -61,6 +67,24 @@ pub fn xgcd(
             return (a_in.abs(), None, None);
         }
     }
+
+    if !extended {
+        let z = biguint_gcd(&a_in.data, &b_in.data);
+        return (BigInt::from_biguint(Plus, z), None, None);
+    }
+
+    if cmp::min(a_in.data.data.len(), b_in.data.data.len()) >= extended_half_gcd_threshold() {
+        // z = |a| * x + |b| * y, so only the signs of x and y need fixing.
+        let (z, mut x, mut y) = half_gcd_extended(&a_in.data, &b_in.data);
+        if a_in.sign == Minus {
+            x = -x;
+        }
+        if b_in.sign == Minus {
+            y = -y;
+        }
+        return (BigInt::from_biguint(Plus, z), Some(x), Some(y));
+    }
+
     lehmer_gcd(a_in, b_in, extended)
 }
 
@@ -105,7 +129,7 @@ fn lehmer_gcd(
     // loop invariant A >= B
     while b.len() > 1 {
         // Attempt to calculate in single-precision using leading words of a and b.
-        let (u0, u1, v0, v1, even) = lehmer_simulate(&a, &b);
+        let (u0, u1, v0, v1, even) = lehmer_simulate(a.digits(), b.digits(), 0);
 
         // multiprecision step
         if v0 != 0 {
@@ -265,6 +289,16 @@ pub fn extended_gcd(
         }
     }
 
+    if !extended {
+        let z = biguint_gcd(&a_in, &b_in);
+        return (BigInt::from_biguint(Plus, z), None, None);
+    }
+
+    if cmp::min(a_in.data.len(), b_in.data.len()) >= extended_half_gcd_threshold() {
+        let (z, x, y) = half_gcd_extended(&a_in, &b_in);
+        return (BigInt::from_biguint(Plus, z), Some(x), Some(y));
+    }
+
     let a_in = a_in.to_bigint().unwrap();
     let b_in = b_in.to_bigint().unwrap();
 
@@ -288,7 +322,7 @@ pub fn extended_gcd(
 
     while b.len() > 1 {
         // Attempt to calculate in single-precision using leading words of a and b.
-        let (u0, u1, v0, v1, even) = lehmer_simulate(&a, &b);
+        let (u0, u1, v0, v1, even) = lehmer_simulate(a.digits(), b.digits(), 0);
 
         // multiprecision step
         if v0 != 0 {
@@ -405,8 +439,15 @@ pub fn extended_gcd(
 /// is used to track the sign of cosequences.
 /// For even iterations: `u0, v1 >= 0 && u1, v0 <= 0`
 /// For odd iterations: `u0, v1 <= && u1, v0 >= 0`
+///
+/// With a nonzero `floor`, it also stops before the updated `b` could be less than
+/// `floor * 2^e`, where `2^e` is the weight of the lowest bit in the leading digit of `a`.
 #[inline]
-fn lehmer_simulate(a: &BigInt, b: &BigInt) -> (BigDigit, BigDigit, BigDigit, BigDigit, bool) {
+fn lehmer_simulate(
+    a: &[BigDigit],
+    b: &[BigDigit],
+    floor: BigDigit,
+) -> (BigDigit, BigDigit, BigDigit, BigDigit, bool) {
     // m >= 2
     let m = b.len();
     // n >= m >= 2
@@ -419,17 +460,16 @@ fn lehmer_simulate(a: &BigInt, b: &BigInt) -> (BigDigit, BigDigit, BigDigit, Big
     // debug_assert!(n >= m);
 
     // extract the top word of bits from a and b
-    let h = a.digits()[n - 1].leading_zeros();
+    let h = a[n - 1].leading_zeros();
 
-    let mut a1: BigDigit = a.digits()[n - 1] << h
-        | ((a.digits()[n - 2] as DoubleBigDigit) >> (BITS as u32 - h)) as BigDigit;
+    let mut a1: BigDigit =
+        a[n - 1] << h | ((a[n - 2] as DoubleBigDigit) >> (BITS as u32 - h)) as BigDigit;
 
     // b may have implicit zero words in the high bits if the lengths differ
     let mut a2: BigDigit = if n == m {
-        b.digits()[n - 1] << h
-            | ((b.digits()[n - 2] as DoubleBigDigit) >> (BITS as u32 - h)) as BigDigit
+        b[n - 1] << h | ((b[n - 2] as DoubleBigDigit) >> (BITS as u32 - h)) as BigDigit
     } else if n == m + 1 {
-        ((b.digits()[n - 2] as DoubleBigDigit) >> (BITS as u32 - h)) as BigDigit
+        ((b[n - 2] as DoubleBigDigit) >> (BITS as u32 - h)) as BigDigit
     } else {
         0
     };
@@ -446,7 +486,8 @@ fn lehmer_simulate(a: &BigInt, b: &BigInt) -> (BigDigit, BigDigit, BigDigit, Big
     let mut v2 = 1;
 
     // Calculate the quotient and cosequences using Collins' stoppting condition.
-    while a2 >= v2 && a1.wrapping_sub(a2) >= v1 + v2 {
+    // The true value of a2 is more than (a2 - v2) * 2^e.
+    while a2 >= v2 && a1.wrapping_sub(a2) >= v1 + v2 && a2 - v2 >= floor {
         let q = a1 / a2;
         let r = a1 % a2;
 
@@ -543,6 +584,370 @@ fn euclid_udpate(
     }
 }
 
+/// Returns the greatest common divisor of `a` and `b`, without computing any Bezout
+/// coefficients.
+///
+/// While the smaller number has at least `Thresholds::gcd_half_gcd` digits, the numbers are
+/// reduced by the subquadratic half-GCD, the rest is done by Lehmer's algorithm, directly on
+/// `BigUint`s.
+pub fn biguint_gcd(a: &BigUint, b: &BigUint) -> BigUint {
+    let mut x = a.clone();
+    let mut y = b.clone();
+
+    while cmp::min(x.data.len(), y.data.len()) >= half_gcd_threshold() {
+        if can_half_gcd(&x, &y) {
+            let (_, x1, y1) = half_gcd(&x, &y);
+            x = x1;
+            y = y1;
+        }
+
+        // One more division step leaves the smaller number at most half as long as the
+        // larger one was before.
+        if x >= y {
+            x %= &y;
+        } else {
+            y %= &x;
+        }
+    }
+
+    loop {
+        if x < y {
+            mem::swap(&mut x, &mut y);
+        }
+
+        if y.data.len() <= 1 {
+            if y.is_zero() {
+                return x;
+            }
+
+            // a and b are both single word
+            x %= &y;
+            let mut a_word = y.data[0];
+            let mut b_word = x.data.first().cloned().unwrap_or(0);
+            while b_word != 0 {
+                let r = a_word % b_word;
+                a_word = b_word;
+                b_word = r;
+            }
+            return BigUint::from_slice_native(&[a_word]);
+        }
+
+        if !lehmer_step(&mut x, &mut y, None, None) {
+            // Single-digit calculations failed to simulate any quotients.
+            x %= &y;
+        }
+    }
+}
+
+/// Extended GCD of nonzero `a` and `b` using the half-GCD, for large numbers. Returns
+/// `(z, x, y)` with `z = a * x + b * y` and the same (minimal) coefficients as Euclid's
+/// algorithm, `|x| <= b / 2z`.
+fn half_gcd_extended(a: &BigUint, b: &BigUint) -> (BigUint, BigInt, BigInt) {
+    let mut x = a.clone();
+    let mut y = b.clone();
+
+    // (a, b) = u (x, y)
+    let mut u = Matrix::identity();
+
+    while cmp::min(x.data.len(), y.data.len()) >= extended_half_gcd_threshold() {
+        if can_half_gcd(&x, &y) {
+            let (m, x1, y1) = half_gcd(&x, &y);
+            x = x1;
+            y = y1;
+            u = u.mul(&m);
+        }
+
+        if x >= y {
+            let (q, r) = x.div_rem(&y);
+            x = r;
+            u.sub_y_from_x(&q);
+        } else {
+            let (q, r) = y.div_rem(&x);
+            y = r;
+            u.sub_x_from_y(&q);
+        }
+    }
+
+    // The rest is done by Lehmer's algorithm, and with (x, y) = u^-1 (a, b) its
+    // coefficients for x and y are converted to ones for a and b.
+    let (z, s, t) = extended_gcd(Cow::Owned(x), Cow::Owned(y), true);
+    let (s, t) = (s.unwrap(), t.unwrap());
+    let int = |n: &BigUint| BigInt::from_biguint(Plus, n.clone());
+    let s_a = &s * int(&u.d) - &t * int(&u.c);
+
+    let z = z.data;
+    let b_z = int(&(b / &z));
+    let mut x = s_a.mod_floor(&b_z);
+    if &x * 2 > b_z {
+        x -= &b_z;
+    }
+    let y = (int(&z) - &x * int(a)) / int(b);
+
+    (z, x, y)
+}
+
+/// Returns whether `half_gcd` can reduce `(x, y)`, which needs the smaller number to have
+/// more than half the bits of the larger one.
+fn can_half_gcd(x: &BigUint, y: &BigUint) -> bool {
+    let n = cmp::max(x.bits(), y.bits());
+    cmp::min(x.bits(), y.bits()) > n / 2 + 1
+}
+
+/// A 2x2 matrix `[[a, b], [c, d]]` with nonnegative entries and determinant 1, which maps the
+/// results `(x', y')` of a reduction back to its inputs: `(x, y) = M (x', y')`. Any such
+/// reduction keeps the gcd.
+#[derive(Clone, Debug)]
+struct Matrix {
+    a: BigUint,
+    b: BigUint,
+    c: BigUint,
+    d: BigUint,
+}
+
+impl Matrix {
+    fn identity() -> Self {
+        Matrix {
+            a: BigUint::one(),
+            b: BigUint::zero(),
+            c: BigUint::zero(),
+            d: BigUint::one(),
+        }
+    }
+
+    /// Returns `M^-1 (x, y) = (d x - b y, a y - c x)`, which must not be negative.
+    fn apply_inverse(&self, x: &BigUint, y: &BigUint) -> (BigUint, BigUint) {
+        (&self.d * x - &self.b * y, &self.a * y - &self.c * x)
+    }
+
+    /// Returns `M N`.
+    fn mul(&self, n: &Matrix) -> Matrix {
+        Matrix {
+            a: &self.a * &n.a + &self.b * &n.c,
+            b: &self.a * &n.b + &self.b * &n.d,
+            c: &self.c * &n.a + &self.d * &n.c,
+            d: &self.c * &n.b + &self.d * &n.d,
+        }
+    }
+
+    /// Records the step `x' = x - q y`, i.e. multiplies by `[[1, q], [0, 1]]`.
+    fn sub_y_from_x(&mut self, q: &BigUint) {
+        self.b += q * &self.a;
+        self.d += q * &self.c;
+    }
+
+    /// Records the step `y' = y - q x`, i.e. multiplies by `[[1, 0], [q, 1]]`.
+    fn sub_x_from_y(&mut self, q: &BigUint) {
+        self.a += q * &self.b;
+        self.c += q * &self.d;
+    }
+
+    /// Multiplies by `[[t, q], [r, p]]`.
+    fn mul_digits(&mut self, t: BigDigit, q: BigDigit, r: BigDigit, p: BigDigit) {
+        *self = Matrix {
+            a: mul_add_digits(&self.a, t, &self.b, r),
+            b: mul_add_digits(&self.a, q, &self.b, p),
+            c: mul_add_digits(&self.c, t, &self.d, r),
+            d: mul_add_digits(&self.c, q, &self.d, p),
+        };
+    }
+}
+
+/// Half-GCD, see Möller, "On Schönhage's algorithm and subquadratic integer gcd computation",
+/// Mathematics of Computation 77 (2008).
+///
+/// For `n = max(bits(a), bits(b))` and `s = n / 2 + 1`, returns `(M, x, y)` with
+/// `(a, b) = M (x, y)`, where `x` and `y` are both at least `2^s` but differ by less than
+/// `2^s`, so that any further Euclidean step would go below `2^s`. Unless the smaller input
+/// is already less than `2^s`, in which case `M` is the identity.
+///
+/// The entries of `M` are then less than `2^(n - s)`, so `x` and `y` have around `n / 2`
+/// bits, if the Euclidean algorithm doesn't run into a large quotient at that point.
+fn half_gcd(a: &BigUint, b: &BigUint) -> (Matrix, BigUint, BigUint) {
+    let n = cmp::max(a.bits(), b.bits());
+    let s = n / 2 + 1;
+
+    let mut m = Matrix::identity();
+    let mut x = a.clone();
+    let mut y = b.clone();
+    if cmp::min(x.bits(), y.bits()) <= s {
+        return (m, x, y);
+    }
+
+    if n >= extended_half_gcd_threshold() * BITS {
+        // Reduce the top n - s bits first. Their results are at least 2^s1 for
+        // s1 = (n - s) / 2 + 1, and the entries of m less than 2^(n - s - s1) <= 2^(s1 - 1),
+        // so applied to the full numbers the results are still more than 2^(s + s1 - 1).
+        let (m1, _, _) = half_gcd(&(a >> s), &(b >> s));
+        let (x1, y1) = m1.apply_inverse(a, b);
+        x = x1;
+        y = y1;
+        m = m1;
+
+        // Some plain steps in case that stopped early, to get below 3n/4 bits.
+        if half_gcd_steps(&mut m, &mut x, &mut y, s, s + n / 4) {
+            return (m, x, y);
+        }
+
+        // Then the top 2 (n2 - s) bits, shifted by p = 2s - n2, whose results applied to the
+        // full numbers are at least 2^(p + (n2 - s + 1) - 1) = 2^s by the same argument.
+        let n2 = cmp::max(x.bits(), y.bits());
+        let p = 2 * s - n2;
+        let (m2, _, _) = half_gcd(&(&x >> p), &(&y >> p));
+        let (x2, y2) = m2.apply_inverse(&x, &y);
+        x = x2;
+        y = y2;
+        m = m.mul(&m2);
+    }
+
+    half_gcd_steps(&mut m, &mut x, &mut y, s, 0);
+    (m, x, y)
+}
+
+/// Does Euclidean steps on `(x, y)` (both at least `2^s`) and records them in `m`, without
+/// going below `2^s`: until `x` and `y` differ by less than `2^s`, in which case it returns
+/// true, or until both have at most `max_bits` bits.
+fn half_gcd_steps(
+    m: &mut Matrix,
+    x: &mut BigUint,
+    y: &mut BigUint,
+    s: usize,
+    max_bits: usize,
+) -> bool {
+    loop {
+        if cmp::max(x.bits(), y.bits()) <= max_bits {
+            return false;
+        }
+
+        // Simulate as many steps as possible with single digits.
+        if lehmer_step(x, y, Some(&mut *m), Some(s)) {
+            continue;
+        }
+
+        let x_larger = *x >= *y;
+        let (big, small) = if x_larger {
+            (&mut *x, &*y)
+        } else {
+            (&mut *y, &*x)
+        };
+        let diff = &*big - small;
+        if diff.bits() <= s {
+            return true;
+        }
+
+        // The largest q with big - q * small >= 2^s, which is the Euclidean quotient unless
+        // the remainder would be less than 2^s.
+        let q = (diff - (BigUint::one() << s)) / small + 1u32;
+        *big -= &q * small;
+        if x_larger {
+            m.sub_y_from_x(&q);
+        } else {
+            m.sub_x_from_y(&q);
+        }
+    }
+}
+
+/// Does the Euclidean steps that `lehmer_simulate` finds for the leading digits of `x` and `y`
+/// (in either order) at once, and records them in `m`. With `floor_bits`, only the steps whose
+/// remainders are at least `2^floor_bits`. Returns false, without changing anything, if there
+/// were no such steps.
+fn lehmer_step(
+    x: &mut BigUint,
+    y: &mut BigUint,
+    m: Option<&mut Matrix>,
+    floor_bits: Option<usize>,
+) -> bool {
+    let swapped = *x < *y;
+    let (big, small) = if swapped { (&*y, &*x) } else { (&*x, &*y) };
+    if small.data.len() < 2 {
+        return false;
+    }
+
+    // the weight of the lowest bit of the leading digit that is simulated
+    let n = big.data.len();
+    let e = n * BITS - big.data[n - 1].leading_zeros() as usize - BITS;
+    let floor = match floor_bits {
+        None => 0,
+        Some(f) if f < e => 1,
+        Some(f) if f - e < BITS => 1 << (f - e),
+        Some(_) => return false,
+    };
+
+    let (u0, u1, v0, v1, even) = lehmer_simulate(&big.data, &small.data, floor);
+    if v0 == 0 {
+        return false;
+    }
+
+    // The new (big, small), and the matrix [[t, q], [r, p]] that maps them back. For an odd
+    // number of steps the larger result replaces the old smaller number.
+    let (big_new, small_new, [t, q, r, p]) = if even {
+        let big_new = mul_sub_digits(big, u0, small, v0);
+        (
+            big_new,
+            mul_sub_digits(small, v1, big, u1),
+            [v1, v0, u1, u0],
+        )
+    } else {
+        let big_new = mul_sub_digits(big, u1, small, v1);
+        (
+            big_new,
+            mul_sub_digits(small, v0, big, u0),
+            [v0, v1, u0, u1],
+        )
+    };
+    debug_assert!(cmp::min(&big_new, &small_new).bits() > floor_bits.unwrap_or(0));
+
+    if swapped {
+        if let Some(m) = m {
+            m.mul_digits(p, r, q, t);
+        }
+        *y = big_new;
+        *x = small_new;
+    } else {
+        if let Some(m) = m {
+            m.mul_digits(t, q, r, p);
+        }
+        *x = big_new;
+        *y = small_new;
+    }
+    true
+}
+
+/// Returns `p x + q y`, in a single pass.
+fn mul_add_digits(x: &BigUint, p: BigDigit, y: &BigUint, q: BigDigit) -> BigUint {
+    let len = cmp::max(x.data.len(), y.data.len());
+    let mut data = SmallVec::with_capacity(len + 2);
+
+    let (mut carry_x, mut carry_y, mut carry) = (0, 0, 0);
+    for i in 0..len {
+        let xi = mac_with_carry(0, x.data.get(i).cloned().unwrap_or(0), p, &mut carry_x);
+        let yi = mac_with_carry(0, y.data.get(i).cloned().unwrap_or(0), q, &mut carry_y);
+        data.push(adc(xi, yi, &mut carry));
+    }
+    let hi: DoubleBigDigit = carry_x + carry_y + carry;
+    data.push(hi as BigDigit);
+    data.push((hi >> BITS) as BigDigit);
+
+    BigUint::new_native(data)
+}
+
+/// Returns `p x - q y`, which must not be negative, in a single pass.
+fn mul_sub_digits(x: &BigUint, p: BigDigit, y: &BigUint, q: BigDigit) -> BigUint {
+    let len = cmp::max(x.data.len(), y.data.len());
+    let mut data = SmallVec::with_capacity(len + 1);
+
+    let (mut carry_x, mut carry_y, mut borrow) = (0, 0, 0);
+    for i in 0..len {
+        let xi = mac_with_carry(0, x.data.get(i).cloned().unwrap_or(0), p, &mut carry_x);
+        let yi = mac_with_carry(0, y.data.get(i).cloned().unwrap_or(0), q, &mut carry_y);
+        data.push(sbb(xi, yi, &mut borrow));
+    }
+    let hi = carry_x as SignedDoubleBigDigit - carry_y as SignedDoubleBigDigit + borrow;
+    debug_assert!(hi >= 0 && hi >> BITS == 0);
+    data.push(hi as BigDigit);
+
+    BigUint::new_native(data)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -748,4 +1153,29 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_half_gcd() {
+        let mut rng = XorShiftRng::from_seed([1u8; 16]);
+
+        for &(a_bits, b_bits, c_bits) in &[
+            (9000, 9000, 0),
+            (12000, 7000, 0),
+            (8000, 8000, 3000),
+            (6000, 300, 5000),
+        ] {
+            let c = rng.gen_biguint(c_bits) + 1u32;
+            let a = rng.gen_biguint(a_bits) * &c;
+            let b = rng.gen_biguint(b_bits) * &c;
+
+            let expected = extended_gcd_euclid(Cow::Borrowed(&a), Cow::Borrowed(&b));
+            assert_eq!(BigInt::from_biguint(Plus, biguint_gcd(&a, &b)), expected.0);
+
+            let (z, x, y) = half_gcd_extended(&a, &b);
+            assert_eq!(BigInt::from_biguint(Plus, z), expected.0);
+            assert_eq!(x, expected.1);
+            assert_eq!(y, expected.2);
+        }
+    }
 }
@@ -1,9 +1,15 @@
-use std::cmp;
+use integer::Integer;
+use smallvec::SmallVec;
+use std::cmp::{self, Ordering};
 use std::iter::repeat;
 
-use crate::algorithms::{adc, add2, sub2, sub_sign};
-use crate::big_digit::{BigDigit, DoubleBigDigit, BITS};
-use crate::bigint::Sign::{Minus, NoSign, Plus};
+use crate::algorithms::{adc, add2, mul3, ntt_mul, ntt_sqr, ntt_supports, sbb, sub2, sub_sign};
+use crate::algorithms::{mul_karatsuba_threshold, mul_ntt_threshold, mul_toom3_threshold};
+use crate::algorithms::{
+    sqr_karatsuba_threshold, sqr_long_threshold, sqr_ntt_threshold, sqr_toom3_threshold,
+};
+use crate::big_digit::{self, BigDigit, DoubleBigDigit, SignedDoubleBigDigit, BITS};
+use crate::bigint::Sign::{self, Minus, NoSign, Plus};
 use crate::biguint::IntDigits;
 use crate::{BigInt, BigUint};
 
@@ -37,6 +43,82 @@ pub fn mac_digit(acc: &mut [BigDigit], b: &[BigDigit], c: BigDigit) {
     }
 }
 
+/// Three argument multiply subtract, modulo `B^acc.len()`:
+/// acc -= b * c
+///
+/// acc must have at least b.len() + 1 digits, so that the top digit of b * c fits. Returns
+/// whether b * c was larger than acc, in which case acc holds the two's complement of the
+/// (negative) result.
+fn msc_digit(acc: &mut [BigDigit], b: &[BigDigit], c: BigDigit) -> bool {
+    if c == 0 {
+        return false;
+    }
+
+    let mut carry: DoubleBigDigit = 0;
+    let mut borrow: SignedDoubleBigDigit = 0;
+    let (a_lo, a_hi) = acc.split_at_mut(b.len());
+
+    for (a, &b) in a_lo.iter_mut().zip(b) {
+        carry += (b as DoubleBigDigit) * (c as DoubleBigDigit);
+        *a = sbb(*a, carry as BigDigit, &mut borrow);
+        carry >>= BITS;
+    }
+
+    for a in a_hi {
+        *a = sbb(*a, carry as BigDigit, &mut borrow);
+        carry = 0;
+        if borrow == 0 {
+            break;
+        }
+    }
+
+    borrow != 0
+}
+
+/// Three argument multiply subtract, modulo `B^acc.len()`:
+/// acc -= b * c
+///
+/// acc must have at least b.len() + c.len() digits. Returns whether b * c was larger than acc,
+/// in which case acc holds the two's complement of the (negative) result, see
+/// `negate_digits`.
+pub(crate) fn msc3(acc: &mut [BigDigit], b: &[BigDigit], c: &[BigDigit]) -> bool {
+    let (x, y) = if b.len() < c.len() { (b, c) } else { (c, b) };
+
+    // The value decreases with every step, so it wraps around at most once.
+    if x.len() < mul_karatsuba_threshold() {
+        let mut wrapped = false;
+        for (i, xi) in x.iter().enumerate() {
+            wrapped |= msc_digit(&mut acc[i..], y, *xi);
+        }
+        return wrapped;
+    }
+
+    // There is no subtracting variant of the faster algorithms, so compute the product first.
+    let prod = mul3(x, y);
+    let mut borrow: SignedDoubleBigDigit = 0;
+    let (a_lo, a_hi) = acc.split_at_mut(prod.data.len());
+
+    for (a, &p) in a_lo.iter_mut().zip(prod.data.iter()) {
+        *a = sbb(*a, p, &mut borrow);
+    }
+    for a in a_hi {
+        if borrow == 0 {
+            break;
+        }
+        *a = sbb(*a, 0, &mut borrow);
+    }
+
+    borrow != 0
+}
+
+/// Replaces the digits of a by their two's complement, B^a.len() - a.
+pub(crate) fn negate_digits(a: &mut [BigDigit]) {
+    let mut carry = 1;
+    for d in a.iter_mut() {
+        *d = adc(!*d, 0, &mut carry);
+    }
+}
+
 /// Three argument multiply accumulate:
 /// acc += b * c
 pub fn mac3(acc: &mut [BigDigit], b: &[BigDigit], c: &[BigDigit]) {
@@ -47,18 +129,50 @@ pub fn mac3(acc: &mut [BigDigit], b: &[BigDigit], c: &[BigDigit]) {
     // - For small inputs, long multiplication is fastest.
     // - Next we use Karatsuba multiplication (Toom-2), which we have optimized
     //   to avoid unnecessary allocations for intermediate values.
-    // - For the largest inputs we use Toom-3, which better optimizes the
+    // - For large inputs we use Toom-3, which better optimizes the
     //   number of operations, but uses more temporary allocations.
+    // - For very large inputs we use NTT multiplication, which is quasi-linear.
+    //   Products that are too large for a single transform are split up by
+    //   Toom-3, whose smaller products then use the NTT again.
+    //
+    // Karatsuba and Toom-3 split both inputs at the same point, which wastes
+    // most of their savings on very unbalanced inputs, so those are first cut
+    // into balanced products (see `unbalanced`).
     //
-    // The thresholds are somewhat arbitrary, chosen by evaluating the results
-    // of `cargo bench --bench bigint multiply`.
+    // The thresholds can be tuned at runtime, see `Thresholds`.
 
-    if x.len() <= 32 {
+    let ntt = mul_ntt_threshold();
+    if x.len() < mul_karatsuba_threshold() {
         long(acc, x, y)
-    } else if x.len() <= 256 {
+    } else if y.len() >= 2 * x.len() && x.len() < ntt {
+        unbalanced(acc, x, y)
+    } else if x.len() < mul_toom3_threshold() {
         karatsuba(acc, x, y)
-    } else {
+    } else if x.len() < ntt || !ntt_supports(x.len() + y.len()) {
         toom3(acc, x, y)
+    } else {
+        ntt_mul(acc, x, y)
+    }
+}
+
+/// Two argument square accumulate:
+/// acc += x * x
+pub fn mac_sqr(acc: &mut [BigDigit], x: &[BigDigit]) {
+    // The same three algorithms and thresholds as in `mac3`, but each of them specialized to
+    // squaring: long squaring only computes about half of the partial products, and the
+    // intermediate products of Karatsuba and Toom-3 are all squares again.
+
+    if x.len() < sqr_long_threshold() {
+        // the extra passes of long_sqr only pay off from some length on
+        long(acc, x, x)
+    } else if x.len() < sqr_karatsuba_threshold() {
+        long_sqr(acc, x)
+    } else if x.len() < sqr_toom3_threshold() {
+        karatsuba_sqr(acc, x)
+    } else if x.len() < sqr_ntt_threshold() || !ntt_supports(2 * x.len()) {
+        toom3_sqr(acc, x)
+    } else {
+        ntt_sqr(acc, x)
     }
 }
 
@@ -69,6 +183,186 @@ fn long(acc: &mut [BigDigit], x: &[BigDigit], y: &[BigDigit]) {
     }
 }
 
+/// Long squaring:
+///
+/// Every cross product x[i] * x[j] with i != j appears twice in x * x, so we only compute
+/// the ones with i < j, and then add them to acc doubled, together with the squares
+/// x[i] * x[i] on the diagonal.
+fn long_sqr(acc: &mut [BigDigit], x: &[BigDigit]) {
+    let mut t: SmallVec<[BigDigit; 64]> = smallvec![0; 2 * x.len()];
+
+    for (i, xi) in x.iter().enumerate() {
+        mac_digit(&mut t[2 * i + 1..], &x[i + 1..], *xi);
+    }
+
+    // acc[j] + 2 * t[j] + the diagonal digit + carry always fits in a DoubleBigDigit:
+    let mut carry: DoubleBigDigit = 0;
+    let mut shifted_out = 0;
+    for (i, xi) in x.iter().enumerate() {
+        let sq = (*xi as DoubleBigDigit) * (*xi as DoubleBigDigit);
+        let (sq_hi, sq_lo) = big_digit::from_doublebigdigit(sq);
+
+        for (j, d) in [(2 * i, sq_lo), (2 * i + 1, sq_hi)].iter().cloned() {
+            let doubled = (t[j] << 1) | shifted_out;
+            shifted_out = t[j] >> (BITS - 1);
+
+            carry += acc[j] as DoubleBigDigit + doubled as DoubleBigDigit + d as DoubleBigDigit;
+            acc[j] = carry as BigDigit;
+            carry >>= BITS;
+        }
+    }
+
+    let mut a = acc[2 * x.len()..].iter_mut();
+    while carry != 0 {
+        let a = a.next().expect("carry overflow during multiplication!");
+        *a = adc(*a, 0, &mut carry);
+    }
+}
+
+/// Blockwise multiplication of unbalanced inputs:
+///
+/// y is cut into slices with the same length as x (apart from the last one), and each of them
+/// is multiplied with x separately:
+///
+/// x * y = x * y0 + x * y1 * b + x * y2 * b^2 + ...
+///
+/// where b = big_digit::BASE^x.len(). Each of those products is balanced, so it can use the
+/// algorithm best suited to x.len().
+fn unbalanced(acc: &mut [BigDigit], x: &[BigDigit], y: &[BigDigit]) {
+    for (i, yi) in y.chunks(x.len()).enumerate() {
+        mac3(&mut acc[i * x.len()..], x, yi);
+    }
+}
+
+/// Like `mac3`, but without any allocations: acc += b * c
+///
+/// Only long and Karatsuba multiplication are used, and all intermediate values are stored in
+/// `scratch`, which must have at least `mac3_scratch_len(max(b.len(), c.len()), karatsuba)`
+/// digits. acc must have at least `b.len() + c.len() + 1` digits.
+///
+/// `karatsuba` is the Karatsuba threshold, which is passed down explicitly so that it can't
+/// change between sizing `scratch` and using it.
+pub(crate) fn mac3_scratch(
+    acc: &mut [BigDigit],
+    b: &[BigDigit],
+    c: &[BigDigit],
+    scratch: &mut [BigDigit],
+    karatsuba: usize,
+) {
+    let (x, y) = if b.len() < c.len() { (b, c) } else { (c, b) };
+
+    if x.len() < karatsuba {
+        long(acc, x, y)
+    } else if y.len() >= 2 * x.len() {
+        for (i, yi) in y.chunks(x.len()).enumerate() {
+            mac3_scratch(&mut acc[i * x.len()..], x, yi, scratch, karatsuba);
+        }
+    } else {
+        karatsuba_scratch(acc, x, y, scratch, karatsuba)
+    }
+}
+
+/// Returns the scratch space `mac3_scratch` needs for factors with at most `len` digits.
+///
+/// Each level of the Karatsuba recursion uses at most 2 * len + 3 digits, where len is the
+/// length of its larger factor. As the smaller factor has more than len / 2 digits (otherwise it
+/// would have been cut into blocks), the larger factor of the next level has at most
+/// len - ceil(floor(len / 2) / 2) digits.
+pub(crate) fn mac3_scratch_len(mut len: usize, karatsuba: usize) -> usize {
+    let mut total = 0;
+    while len >= cmp::max(karatsuba, 2) {
+        total += 2 * len + 3;
+        len -= Integer::div_ceil(&(len / 2), &2);
+    }
+    total
+}
+
+/// `karatsuba` for `mac3_scratch`, with p, x1 - x0 and y1 - y0 at the start of `scratch`.
+///
+/// The differences keep the lengths of x1 and y1, so all recursive calls get factors of
+/// known lengths, possibly with leading zeros.
+fn karatsuba_scratch(
+    acc: &mut [BigDigit],
+    x: &[BigDigit],
+    y: &[BigDigit],
+    scratch: &mut [BigDigit],
+    karatsuba: usize,
+) {
+    let b = x.len() / 2;
+    let (x0, x1) = x.split_at(b);
+    let (y0, y1) = y.split_at(b);
+
+    let (p, scratch) = scratch.split_at_mut(x1.len() + y1.len() + 1);
+    let (j0, scratch) = scratch.split_at_mut(x1.len());
+    let (j1, scratch) = scratch.split_at_mut(y1.len());
+
+    // p2 = x1 * y1
+    zero(p);
+    mac3_scratch(p, x1, y1, scratch, karatsuba);
+
+    add2(&mut acc[b..], p);
+    add2(&mut acc[b * 2..], p);
+
+    // p0 = x0 * y0
+    zero(p);
+    mac3_scratch(p, x0, y0, scratch, karatsuba);
+
+    add2(&mut acc[..], p);
+    add2(&mut acc[b..], p);
+
+    // p1 = (x1 - x0) * (y1 - y0)
+    let j0_sign = sub_sign_into(j0, x1, x0);
+    let j1_sign = sub_sign_into(j1, y1, y0);
+
+    match j0_sign * j1_sign {
+        Plus => {
+            zero(p);
+            mac3_scratch(p, j0, j1, scratch, karatsuba);
+
+            sub2(&mut acc[b..], p);
+        }
+        Minus => {
+            mac3_scratch(&mut acc[b..], j0, j1, scratch, karatsuba);
+        }
+        NoSign => (),
+    }
+}
+
+fn zero(a: &mut [BigDigit]) {
+    for ai in a.iter_mut() {
+        *ai = 0;
+    }
+}
+
+/// Sets `d` (which has the length of `a`) to |a - b| and returns the sign of a - b, for
+/// `a.len() >= b.len()`. Unlike `sub_sign`, both may have leading zeros.
+fn sub_sign_into(d: &mut [BigDigit], a: &[BigDigit], b: &[BigDigit]) -> Sign {
+    let (a_lo, a_hi) = a.split_at(b.len());
+
+    let ord = if a_hi.iter().any(|&ai| ai != 0) {
+        Ordering::Greater
+    } else {
+        a_lo.iter().rev().cmp(b.iter().rev())
+    };
+
+    match ord {
+        Ordering::Greater => {
+            d.copy_from_slice(a);
+            sub2(d, b);
+            Plus
+        }
+        Ordering::Less => {
+            // a < b, so b - a fits into b.len() digits
+            let (d_lo, d_hi) = d.split_at_mut(b.len());
+            d_lo.copy_from_slice(b);
+            sub2(d_lo, a_lo);
+            zero(d_hi);
+            Minus
+        }
+        Ordering::Equal => NoSign,
+    }
+}
+
 /// Karatsuba multiplication:
 ///
 /// The idea is that we break x and y up into two smaller numbers that each have about half
@@ -185,6 +479,54 @@ fn karatsuba(acc: &mut [BigDigit], x: &[BigDigit], y: &[BigDigit]) {
     }
 }
 
+/// Karatsuba squaring:
+///
+/// This is `karatsuba` with y = x, so that the three intermediate products are squares:
+///
+/// p0 = x0 * x0
+/// p1 = (x1 - x0) * (x1 - x0)
+/// p2 = x1 * x1
+///
+/// Since p1 can't be negative, it is always subtracted.
+fn karatsuba_sqr(acc: &mut [BigDigit], x: &[BigDigit]) {
+    let b = x.len() / 2;
+    let (x0, x1) = x.split_at(b);
+
+    // x1.len() >= x0.len(), so p is large enough for all three squares:
+    let len = 2 * x1.len() + 1;
+    let mut p = BigUint {
+        data: smallvec![0; len],
+    };
+
+    // p2 = x1 * x1
+    mac_sqr(&mut p.data[..], x1);
+    p.normalize();
+
+    add2(&mut acc[b..], &p.data[..]);
+    add2(&mut acc[b * 2..], &p.data[..]);
+
+    // p0 = x0 * x0
+    p.data.clear();
+    p.data.resize(len, 0);
+
+    mac_sqr(&mut p.data[..], x0);
+    p.normalize();
+
+    add2(&mut acc[..], &p.data[..]);
+    add2(&mut acc[b..], &p.data[..]);
+
+    // p1 = (x1 - x0) * (x1 - x0)
+    let (_, j0) = sub_sign(x1, x0);
+
+    p.data.clear();
+    p.data.resize(len, 0);
+
+    mac_sqr(&mut p.data[..], &j0.data[..]);
+    p.normalize();
+
+    sub2(&mut acc[b..], &p.data[..]);
+}
+
 /// Toom-3 multiplication:
 ///
 /// Toom-3 is like Karatsuba above, but dividing the inputs into three parts.
@@ -267,6 +609,62 @@ fn toom3(acc: &mut [BigDigit], x: &[BigDigit], y: &[BigDigit]) {
     // w(-2)
     let r3 = ((p2 + x2) * 2 - x0) * ((q2 + y2) * 2 - y0);
 
+    toom3_recompose(acc, i, r0, r1, r2, r3, r4);
+}
+
+/// Toom-3 squaring:
+///
+/// This is `toom3` with y = x, so that w(t) is evaluated with five squarings (which the
+/// multiplications below detect and forward to `mac_sqr`).
+fn toom3_sqr(acc: &mut [BigDigit], x: &[BigDigit]) {
+    let i = x.len() / 3 + 1;
+
+    let x0_len = i;
+    let x1_len = cmp::min(x.len() - x0_len, i);
+
+    // x(t) = x2*t^2 + x1*t + x0
+    let x0 = BigInt::from_slice_native(Plus, &x[..x0_len]);
+    let x1 = BigInt::from_slice_native(Plus, &x[x0_len..x0_len + x1_len]);
+    let x2 = BigInt::from_slice_native(Plus, &x[x0_len + x1_len..]);
+
+    // x0 + x2, avoiding temporaries
+    let p = &x0 + &x2;
+
+    // x2 - x1 + x0, avoiding temporaries
+    let p2 = &p - &x1;
+
+    // w(0)
+    let r0 = &x0 * &x0;
+
+    // w(inf)
+    let r4 = &x2 * &x2;
+
+    // w(1)
+    let p1 = p + x1;
+    let r1 = &p1 * &p1;
+
+    // w(-1)
+    let r2 = &p2 * &p2;
+
+    // w(-2)
+    let p3 = (p2 + x2) * 2 - x0;
+    let r3 = &p3 * &p3;
+
+    toom3_recompose(acc, i, r0, r1, r2, r3, r4);
+}
+
+/// Interpolates the product polynomial of `toom3` and `toom3_sqr` from its values
+/// r0 = w(0), r1 = w(1), r2 = w(-1), r3 = w(-2) and r4 = w(inf), and adds it to acc,
+/// evaluated at t = big_digit::BASE^i.
+fn toom3_recompose(
+    acc: &mut [BigDigit],
+    i: usize,
+    r0: BigInt,
+    r1: BigInt,
+    r2: BigInt,
+    r3: BigInt,
+    r4: BigInt,
+) {
     // Evaluating these points gives us the following system of linear equations.
     //
     //  0  0  0  0  1 | a
@@ -307,6 +705,13 @@ fn toom3(acc: &mut [BigDigit], x: &[BigDigit], y: &[BigDigit]) {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "rand")]
+    use crate::bigrand::RandBigInt;
+    #[cfg(feature = "rand")]
+    use rand::{Rng, SeedableRng};
+    #[cfg(feature = "rand")]
+    use rand_xorshift::XorShiftRng;
+
     #[cfg(feature = "u64_digit")]
     #[test]
     fn test_mac3_regression() {
@@ -1669,4 +2074,139 @@ mod tests {
         toom3(&mut a3, &b, &c);
         assert_eq!(a1, a3);
     }
+
+    #[test]
+    fn test_sqr() {
+        // Deterministic digits with all bits in use, including all-ones digits that stress
+        // the carries of the doubled cross products.
+        let mut state: BigDigit = 0x2545_f491;
+        let mut digit = || {
+            state = state
+                .wrapping_mul(0x5851_f42d)
+                .wrapping_add(0x1405_7b7e)
+                .rotate_left(7);
+            if state & 7 == 0 {
+                !0
+            } else {
+                state
+            }
+        };
+
+        for &n in &[0, 1, 2, 3, 31, 32, 33, 64, 255, 256, 257, 600] {
+            let x: Vec<BigDigit> = (0..n).map(|_| digit()).collect();
+
+            let mut expected: Vec<BigDigit> = vec![0; 2 * n + 1];
+            long(&mut expected, &x, &x);
+
+            let mut a1: Vec<BigDigit> = vec![0; 2 * n + 1];
+            let mut a2: Vec<BigDigit> = vec![0; 2 * n + 1];
+            let mut a3: Vec<BigDigit> = vec![0; 2 * n + 1];
+            long_sqr(&mut a1, &x);
+            assert_eq!(a1, expected);
+            if n >= 2 {
+                karatsuba_sqr(&mut a2, &x);
+                assert_eq!(a2, expected);
+            }
+            if n >= 3 {
+                toom3_sqr(&mut a3, &x);
+                assert_eq!(a3, expected);
+            }
+
+            // accumulating into a non-zero acc
+            let mut acc = expected.clone();
+            mac_sqr(&mut acc, &x);
+            let mut twice = expected.clone();
+            add2(&mut twice, &expected);
+            assert_eq!(acc, twice);
+        }
+
+        let x = [!0; 40];
+        let mut expected: Vec<BigDigit> = vec![0; 81];
+        long(&mut expected, &x, &x);
+        let mut acc: Vec<BigDigit> = vec![0; 81];
+        mac_sqr(&mut acc, &x);
+        assert_eq!(acc, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_unbalanced() {
+        let mut rng = XorShiftRng::from_seed([1u8; 16]);
+
+        for _ in 0..20 {
+            let x_len = rng.gen_range(33, 300);
+            let y_len = rng.gen_range(2 * x_len, 10 * x_len);
+            let x = rng.gen_biguint(x_len * BITS);
+            let y = rng.gen_biguint(y_len * BITS);
+            let (x, y) = (x.digits(), y.digits());
+
+            let mut a1: Vec<BigDigit> = vec![0; x.len() + y.len() + 1];
+            let mut a2: Vec<BigDigit> = vec![0; x.len() + y.len() + 1];
+            long(&mut a1, x, y);
+            unbalanced(&mut a2, x, y);
+            assert_eq!(a1, a2);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_ntt_toom3() {
+        let mut rng = XorShiftRng::from_seed([1u8; 16]);
+
+        for _ in 0..20 {
+            let x_len = rng.gen_range(3, 3000);
+            let y_len = rng.gen_range(x_len, 4000);
+            let x = rng.gen_biguint(x_len * BITS);
+            let y = rng.gen_biguint(y_len * BITS);
+            let (x, y) = (x.digits(), y.digits());
+
+            let mut a1: Vec<BigDigit> = vec![0; x.len() + y.len() + 1];
+            let mut a2: Vec<BigDigit> = vec![0; x.len() + y.len() + 1];
+            toom3(&mut a1, x, y);
+            ntt_mul(&mut a2, x, y);
+            assert_eq!(a1, a2);
+
+            let mut a1: Vec<BigDigit> = vec![0; 2 * x.len() + 1];
+            let mut a2: Vec<BigDigit> = vec![0; 2 * x.len() + 1];
+            toom3(&mut a1, x, x);
+            ntt_sqr(&mut a2, x);
+            assert_eq!(a1, a2);
+        }
+
+        // all bits set gives the largest possible convolution coefficients
+        let x = vec![!0; 3000];
+        let mut a1: Vec<BigDigit> = vec![0; 6001];
+        let mut a2: Vec<BigDigit> = vec![0; 6001];
+        toom3(&mut a1, &x, &x);
+        ntt_sqr(&mut a2, &x);
+        assert_eq!(a1, a2);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_mac3_scratch() {
+        let mut rng = XorShiftRng::from_seed([1u8; 16]);
+
+        for _ in 0..50 {
+            let x_len = rng.gen_range(1, 200);
+            let y_len = rng.gen_range(1, 400);
+            let x = rng.gen_biguint(x_len * BITS);
+            let y = rng.gen_biguint(y_len * BITS);
+            // leading zeros, like the differences in karatsuba_scratch
+            let mut x = x.digits().to_vec();
+            x.resize(x_len, 0);
+            let y = y.digits();
+
+            let mut a1: Vec<BigDigit> = vec![0; x.len() + y.len() + 1];
+            long(&mut a1, &x, y);
+
+            for &karatsuba in &[2, 3, 8, 32] {
+                let len = mac3_scratch_len(cmp::max(x.len(), y.len()), karatsuba);
+                let mut scratch = vec![!0; len];
+                let mut a2: Vec<BigDigit> = vec![0; x.len() + y.len() + 1];
+                mac3_scratch(&mut a2, &x, y, &mut scratch, karatsuba);
+                assert_eq!(a1, a2);
+            }
+        }
+    }
 }
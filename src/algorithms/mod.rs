@@ -9,9 +9,12 @@ mod jacobi;
 mod mac;
 mod mod_inverse;
 mod mul;
+mod ntt;
 mod shl;
 mod shr;
+mod sqrt_mod;
 mod sub;
+mod thresholds;
 
 pub use self::add::*;
 pub use self::bits::*;
@@ -22,6 +25,9 @@ pub use self::jacobi::*;
 pub use self::mac::*;
 pub use self::mod_inverse::*;
 pub use self::mul::*;
+pub use self::ntt::*;
 pub use self::shl::*;
 pub use self::shr::*;
+pub use self::sqrt_mod::*;
 pub use self::sub::*;
+pub use self::thresholds::*;
@@ -1,9 +1,10 @@
 use std::borrow::Cow;
 
-use num_traits::{One, Signed};
+use integer::Integer;
+use num_traits::{One, Signed, Zero};
 
 use crate::algorithms::extended_gcd;
-use crate::{BigInt, BigUint};
+use crate::{BarrettReducer, BigInt, BigUint, IntoBigUint};
 
 /// Calculate the modular inverse of `g`.
 /// Implementation is based on the naive version from wikipedia.
@@ -24,6 +25,67 @@ pub fn mod_inverse(g: Cow<BigUint>, n: Cow<BigUint>) -> Option<BigInt> {
     }
 }
 
+/// Calculate the modular inverses of all `elements` modulo `modulus` at once.
+///
+/// This uses Montgomery's trick: the elements are multiplied together, the
+/// product is inverted, and the individual inverses are recovered from it, so
+/// only one inversion and `3(n - 1)` modular multiplications are needed. The
+/// inverses are returned in the range `[0, modulus)`.
+///
+/// If an element has no inverse, the index of the first such element is
+/// returned as the error.
+///
+/// Panics if the modulus is zero.
+pub fn batch_mod_inverse(elements: &[BigUint], modulus: &BigUint) -> Result<Vec<BigUint>, usize> {
+    assert!(!modulus.is_zero(), "divide by zero!");
+
+    if elements.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ctx = BarrettReducer::new(modulus);
+    let elements: Vec<BigUint> = elements.iter().map(|x| ctx.reduce(x)).collect();
+
+    // prefix[i] = elements[0] * ... * elements[i] mod m
+    let mut prefix = Vec::with_capacity(elements.len());
+    prefix.push(elements[0].clone());
+    for x in &elements[1..] {
+        let p = ctx.mul_mod(&prefix[prefix.len() - 1], x);
+        prefix.push(p);
+    }
+
+    let mut inv = match mod_inverse(
+        Cow::Borrowed(&prefix[elements.len() - 1]),
+        Cow::Borrowed(modulus),
+    ) {
+        Some(inv) => inv.into_biguint().unwrap(),
+        None => {
+            // Once a prefix shares a factor with m all longer ones do, so the
+            // first non-invertible element is found by a binary search.
+            let (mut lo, mut hi) = (0, elements.len() - 1);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if prefix[mid].gcd(modulus).is_one() {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            return Err(lo);
+        }
+    };
+
+    // inv = (elements[0] * ... * elements[i])^-1 at the start of each step
+    let mut res = vec![BigUint::zero(); elements.len()];
+    for i in (1..elements.len()).rev() {
+        res[i] = ctx.mul_mod(&inv, &prefix[i - 1]);
+        inv = ctx.mul_mod(&inv, &elements[i]);
+    }
+    res[0] = inv;
+
+    Ok(res)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,6 +94,7 @@ mod tests {
     use num_traits::FromPrimitive;
 
     use crate::traits::ModInverse;
+    use crate::Sign;
 
     #[test]
     fn test_mod_inverse() {
@@ -92,4 +155,58 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_batch_mod_inverse() {
+        let modulus = BigUint::parse_bytes(b"170141183460469231731687303715884105727", 10).unwrap();
+        let elements: Vec<BigUint> = (1u64..200)
+            .map(|i| (BigUint::from_u64(i * i).unwrap() * 0x9e37_79b9_7f4a_7c15u64) << (i as usize))
+            .collect();
+
+        let inverses = batch_mod_inverse(&elements, &modulus).unwrap();
+        assert_eq!(inverses.len(), elements.len());
+        for (x, inv) in elements.iter().zip(&inverses) {
+            assert!(inv < &modulus);
+            assert_eq!(
+                Some(BigInt::from_biguint(Sign::Plus, inv.clone())),
+                x.mod_inverse(&modulus)
+            );
+        }
+
+        assert_eq!(batch_mod_inverse(&[], &modulus), Ok(Vec::new()));
+        assert_eq!(
+            batch_mod_inverse(&[BigUint::from_u32(5).unwrap()], &BigUint::one()),
+            Ok(vec![BigUint::zero()])
+        );
+
+        // 100 = 2^2 * 5^2
+        let modulus = BigUint::from_u32(100).unwrap();
+        let elements: Vec<BigUint> = [3u32, 7, 11, 13, 203, 17, 10, 2, 0]
+            .iter()
+            .map(|&x| BigUint::from_u32(x).unwrap())
+            .collect();
+        for n in 0..elements.len() {
+            let expected = match n {
+                0..=5 => Ok(()),
+                _ => Err(6),
+            };
+            assert_eq!(
+                batch_mod_inverse(&elements[..=n], &modulus).map(|_| ()),
+                expected,
+                "{}",
+                n
+            );
+        }
+        let first_bad = vec![
+            BigUint::from_u32(40).unwrap(),
+            BigUint::from_u32(3).unwrap(),
+        ];
+        assert_eq!(batch_mod_inverse(&first_bad, &modulus), Err(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "divide by zero!")]
+    fn test_batch_mod_inverse_zero() {
+        let _ = batch_mod_inverse(&[BigUint::one()], &BigUint::zero());
+    }
 }
@@ -1,4 +1,4 @@
-use crate::algorithms::mac3;
+use crate::algorithms::{mac3, mac_sqr};
 use crate::big_digit::{BigDigit, DoubleBigDigit, BITS};
 use crate::BigUint;
 
@@ -11,6 +11,12 @@ pub fn mul_with_carry(a: BigDigit, b: BigDigit, acc: &mut DoubleBigDigit) -> Big
 }
 
 pub fn mul3(x: &[BigDigit], y: &[BigDigit]) -> BigUint {
+    // Squarings are common (and cheaper), catch them here so that `&a * &a` benefits too.
+    // Only the same slice counts, comparing the digits would cost O(n) on every product.
+    if x.as_ptr() == y.as_ptr() && x.len() == y.len() {
+        return sqr3(x);
+    }
+
     let len = x.len() + y.len() + 1;
     let mut prod = BigUint {
         data: smallvec![0; len],
@@ -20,6 +26,16 @@ pub fn mul3(x: &[BigDigit], y: &[BigDigit]) -> BigUint {
     prod.normalized()
 }
 
+pub fn sqr3(x: &[BigDigit]) -> BigUint {
+    let len = 2 * x.len() + 1;
+    let mut prod = BigUint {
+        data: smallvec![0; len],
+    };
+
+    mac_sqr(&mut prod.data[..], x);
+    prod.normalized()
+}
+
 pub fn scalar_mul(a: &mut [BigDigit], b: BigDigit) -> BigDigit {
     let mut carry = 0;
     for a in a.iter_mut() {
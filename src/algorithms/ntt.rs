@@ -0,0 +1,284 @@
+use std::cmp;
+
+use crate::algorithms::add2;
+use crate::big_digit::{BigDigit, BITS};
+
+/// Number theoretic transform (NTT) multiplication:
+///
+/// The inputs are split into 32 bit chunks, which are the coefficients of two polynomials
+/// x(t) and y(t) with t = 2^32. Their product is the cyclic convolution of the coefficients,
+/// which we compute with fast Fourier transforms over the fields Z/pZ for three primes p
+/// that have large powers of two dividing p - 1 (so that there are roots of unity of
+/// the required order).
+///
+/// Each coefficient of the product is less than `len * 2^64`, which is below the product of
+/// the three primes (about 2^89) for every supported length, so it can be reconstructed
+/// exactly from its three residues with the chinese remainder theorem. All arithmetic is
+/// done on u64 values, so this works the same for both digit sizes.
+///
+/// Computes acc += x * y.
+pub fn ntt_mul(acc: &mut [BigDigit], x: &[BigDigit], y: &[BigDigit]) {
+    mac_convolution(acc, x, Some(y));
+}
+
+/// Like `ntt_mul`, but computes acc += x * x, with one forward transform less per prime.
+pub fn ntt_sqr(acc: &mut [BigDigit], x: &[BigDigit]) {
+    mac_convolution(acc, x, None);
+}
+
+/// Returns whether a product with a total of `len` digits can be computed by `ntt_mul` and
+/// `ntt_sqr`.
+///
+/// Transforms are limited to 2^24 points (the largest power of two dividing p - 1 for all three
+/// primes), so products with more than 2^29 bits have to be split up by the caller.
+pub fn ntt_supports(len: usize) -> bool {
+    len <= MAX_LEN / CHUNKS
+}
+
+// The number of 32 bit chunks in a digit.
+const CHUNKS: usize = BITS / 32;
+
+// The largest transform length shared by all three primes.
+const MAX_LEN: usize = 1 << 24;
+
+trait Prime {
+    /// The prime modulus.
+    const P: u64;
+
+    /// A generator of the multiplicative group of Z/PZ.
+    const G: u64;
+}
+
+struct P1;
+struct P2;
+struct P3;
+
+// 15 * 2^27 + 1
+impl Prime for P1 {
+    const P: u64 = 2_013_265_921;
+    const G: u64 = 31;
+}
+
+// 7 * 2^26 + 1
+impl Prime for P2 {
+    const P: u64 = 469_762_049;
+    const G: u64 = 3;
+}
+
+// 45 * 2^24 + 1
+impl Prime for P3 {
+    const P: u64 = 754_974_721;
+    const G: u64 = 11;
+}
+
+#[inline]
+fn mul_mod<P: Prime>(a: u64, b: u64) -> u64 {
+    a * b % P::P
+}
+
+fn pow_mod<P: Prime>(mut a: u64, mut e: u64) -> u64 {
+    let mut r = 1;
+    while e > 0 {
+        if e & 1 == 1 {
+            r = mul_mod::<P>(r, a);
+        }
+        a = mul_mod::<P>(a, a);
+        e >>= 1;
+    }
+    r
+}
+
+/// Returns the powers `w^i` for `i < n`, together with `floor(w^i * 2^32 / P)`, which lets
+/// `mul_shoup` multiply by them without a division (V. Shoup, "NTL: A library for doing
+/// number theory").
+fn twiddles<P: Prime>(w: u32, n: usize, table: &mut Vec<(u32, u32)>) {
+    table.clear();
+    let mut wi = 1;
+    for _ in 0..n {
+        table.push((wi as u32, ((wi << 32) / P::P) as u32));
+        wi = mul_mod::<P>(wi, u64::from(w));
+    }
+}
+
+/// Returns v * w mod P, for a twiddle factor (w, w_shoup) from `twiddles`.
+#[inline]
+fn mul_shoup<P: Prime>(v: u32, (w, w_shoup): (u32, u32)) -> u32 {
+    let q = (u64::from(v) * u64::from(w_shoup)) >> 32;
+    let t = (u64::from(v) * u64::from(w)).wrapping_sub(q * P::P) as u32;
+    if u64::from(t) >= P::P {
+        t - P::P as u32
+    } else {
+        t
+    }
+}
+
+#[inline]
+fn add_mod<P: Prime>(a: u32, b: u32) -> u32 {
+    let s = a + b;
+    if u64::from(s) >= P::P {
+        s - P::P as u32
+    } else {
+        s
+    }
+}
+
+#[inline]
+fn sub_mod<P: Prime>(a: u32, b: u32) -> u32 {
+    if a >= b {
+        a - b
+    } else {
+        a + P::P as u32 - b
+    }
+}
+
+/// Returns a primitive `n`-th root of unity, or its inverse.
+fn root<P: Prime>(n: usize, invert: bool) -> u32 {
+    let w = pow_mod::<P>(P::G, (P::P - 1) / n as u64);
+    if invert {
+        pow_mod::<P>(w, P::P - 2) as u32
+    } else {
+        w as u32
+    }
+}
+
+/// In place forward NTT of `a` (whose length is a power of two), with decimation in frequency.
+///
+/// The result is in bit reversed order, which `inverse_ntt` expects as its input, so the
+/// convolution never needs to reorder anything.
+fn forward_ntt<P: Prime>(a: &mut [u32], table: &mut Vec<(u32, u32)>) {
+    let mut len = a.len();
+    while len >= 2 {
+        let half = len / 2;
+        twiddles::<P>(root::<P>(len, false), half, table);
+
+        for block in a.chunks_mut(len) {
+            let (lo, hi) = block.split_at_mut(half);
+            for ((u, v), w) in lo.iter_mut().zip(hi.iter_mut()).zip(table.iter()) {
+                let (s, t) = (*u, *v);
+                *u = add_mod::<P>(s, t);
+                *v = mul_shoup::<P>(sub_mod::<P>(s, t), *w);
+            }
+        }
+
+        len = half;
+    }
+}
+
+/// In place inverse of `forward_ntt` (including the division by the length), with decimation
+/// in time.
+fn inverse_ntt<P: Prime>(a: &mut [u32], table: &mut Vec<(u32, u32)>) {
+    let n = a.len();
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        twiddles::<P>(root::<P>(len, true), half, table);
+
+        for block in a.chunks_mut(len) {
+            let (lo, hi) = block.split_at_mut(half);
+            for ((u, v), w) in lo.iter_mut().zip(hi.iter_mut()).zip(table.iter()) {
+                let s = *u;
+                let t = mul_shoup::<P>(*v, *w);
+                *u = add_mod::<P>(s, t);
+                *v = sub_mod::<P>(s, t);
+            }
+        }
+
+        len <<= 1;
+    }
+
+    let n_inv = pow_mod::<P>(n as u64, P::P - 2);
+    for ai in a.iter_mut() {
+        *ai = mul_mod::<P>(u64::from(*ai), n_inv) as u32;
+    }
+}
+
+/// Splits `x` into 32 bit chunks, zero padded to `n` entries.
+fn to_chunks(x: &[BigDigit], n: usize) -> Vec<u32> {
+    let mut chunks = Vec::with_capacity(n);
+    for &d in x {
+        for k in 0..CHUNKS {
+            chunks.push((d >> (32 * k)) as u32);
+        }
+    }
+    chunks.resize(n, 0);
+    chunks
+}
+
+/// Returns the cyclic convolution of `x` and `y` (or of `x` with itself) modulo `P`, both of
+/// which have a power of two length.
+fn convolution<P: Prime>(x: &[u32], y: Option<&[u32]>) -> Vec<u32> {
+    let mut table = Vec::with_capacity(x.len() / 2);
+
+    let mut fx: Vec<u32> = x.iter().map(|&c| (u64::from(c) % P::P) as u32).collect();
+    forward_ntt::<P>(&mut fx, &mut table);
+
+    match y {
+        Some(y) => {
+            let mut fy: Vec<u32> = y.iter().map(|&c| (u64::from(c) % P::P) as u32).collect();
+            forward_ntt::<P>(&mut fy, &mut table);
+            for (a, b) in fx.iter_mut().zip(fy.iter()) {
+                *a = mul_mod::<P>(u64::from(*a), u64::from(*b)) as u32;
+            }
+        }
+        None => {
+            for a in fx.iter_mut() {
+                *a = mul_mod::<P>(u64::from(*a), u64::from(*a)) as u32;
+            }
+        }
+    }
+
+    inverse_ntt::<P>(&mut fx, &mut table);
+    fx
+}
+
+fn mac_convolution(acc: &mut [BigDigit], x: &[BigDigit], y: Option<&[BigDigit]>) {
+    let y_len = y.map_or(x.len(), |y| y.len());
+    let prod_len = x.len() + y_len;
+    assert!(ntt_supports(prod_len));
+
+    // The product has (at most) prod_len * CHUNKS chunks, so a transform of this length
+    // doesn't wrap around:
+    let n = cmp::max(prod_len * CHUNKS, 1).next_power_of_two();
+
+    let xc = to_chunks(x, n);
+    let yc = y.map(|y| to_chunks(y, n));
+    let yc = yc.as_ref().map(|y| &y[..]);
+
+    let r1 = convolution::<P1>(&xc, yc);
+    let r2 = convolution::<P2>(&xc, yc);
+    let r3 = convolution::<P3>(&xc, yc);
+
+    // Garner's algorithm: the coefficient is c = a + P1 * (b + P2 * c3), with a < P1,
+    // b < P2, c3 < P3.
+    let p1_inv_p2 = pow_mod::<P2>(P1::P % P2::P, P2::P - 2);
+    let p1p2_inv_p3 = pow_mod::<P3>(P1::P * P2::P % P3::P, P3::P - 2);
+
+    let mut prod: Vec<BigDigit> = Vec::with_capacity(prod_len);
+    let mut digit: BigDigit = 0;
+    // carry holds the bits of the running sum above the current chunk; it stays below 2^59.
+    let mut carry: u64 = 0;
+
+    for i in 0..prod_len * CHUNKS {
+        let a = u64::from(r1[i]);
+        let b = mul_mod::<P2>((u64::from(r2[i]) + P2::P - a % P2::P) % P2::P, p1_inv_p2);
+        let ab = (a + P1::P * b) % P3::P;
+        let c3 = mul_mod::<P3>((u64::from(r3[i]) + P3::P - ab) % P3::P, p1p2_inv_p3);
+        let v = b + P2::P * c3;
+
+        // c + carry = a + P1 * v + carry, computed in 32 bit halves:
+        let lo = P1::P * (v & 0xffff_ffff) + a + (carry & 0xffff_ffff);
+        carry = P1::P * (v >> 32) + (lo >> 32) + (carry >> 32);
+
+        digit |= ((lo & 0xffff_ffff) as BigDigit) << (32 * (i % CHUNKS));
+        if i % CHUNKS == CHUNKS - 1 {
+            prod.push(digit);
+            digit = 0;
+        }
+    }
+    debug_assert_eq!(carry, 0);
+
+    while let Some(&0) = prod.last() {
+        prod.pop();
+    }
+    add2(acc, &prod);
+}
@@ -0,0 +1,535 @@
+use integer::Integer;
+use num_traits::{One, Pow, Zero};
+
+use crate::algorithms::jacobi;
+use crate::traits::ModInverse;
+use crate::{BigInt, BigUint, IntoBigUint, MontgomeryContext};
+
+/// Calculate a square root of `a` modulo the prime `p`.
+///
+/// Returns the smaller of the two roots `r` and `p - r`, or `None` if `a` is
+/// not a quadratic residue. Primes with `p ≡ 3 mod 4` and `p ≡ 5 mod 8` need a
+/// single exponentiation; otherwise Tonelli–Shanks is used, or Cipolla's
+/// algorithm if the 2-adicity of `p - 1` is so high that it is faster.
+///
+/// `p` is not checked for primality. For a composite odd `p` the result is
+/// either `None` or a valid square root.
+///
+/// Panics if `p` is even and not 2 (which includes zero).
+pub fn sqrt_mod_prime(a: &BigUint, p: &BigUint) -> Option<BigUint> {
+    assert!(
+        p.is_odd() || p == &BigUint::from(2u32),
+        "the modulus must be an odd prime or 2"
+    );
+
+    let a = a % p;
+    if a.is_zero() || p == &BigUint::from(2u32) {
+        return Some(a);
+    }
+
+    let a_int = BigInt::from_biguint(crate::Sign::Plus, a.clone());
+    let p_int = BigInt::from_biguint(crate::Sign::Plus, p.clone());
+    if jacobi(&a_int, &p_int) != 1 {
+        return None;
+    }
+
+    let ctx = MontgomeryContext::new(p);
+    let r = match p.data[0] & 7 {
+        3 | 7 => {
+            // a^((p + 1) / 4) squares to a^((p + 1) / 2) = a * a^((p - 1) / 2) = a.
+            ctx.modpow(&a, &((p >> 2) + 1u32))
+        }
+        5 => sqrt_atkin(&ctx, &a),
+        _ => {
+            // All odd squares are 1 mod 8, and have no non-residues to search for.
+            let root = p.sqrt();
+            if &(&root * &root) == p {
+                return None;
+            }
+
+            let s = (p - 1u32).trailing_zeros().unwrap();
+            // Tonelli–Shanks needs up to s^2 / 2 extra multiplications, Cipolla
+            // about two per bit of p more than a plain exponentiation. Crossover
+            // from S. Müller, "On the computation of square roots in finite fields".
+            if s * (s - 1) > 8 * p.bits() + 20 {
+                sqrt_cipolla(&ctx, &a)?
+            } else {
+                sqrt_tonelli_shanks(&ctx, &a, s)?
+            }
+        }
+    };
+
+    // Only possible for composite moduli.
+    if (&r * &r) % p != a {
+        return None;
+    }
+
+    let neg = p - &r;
+    Some(if neg < r { neg } else { r })
+}
+
+/// Atkin's algorithm for `p ≡ 5 mod 8`: with `b = (2a)^((p - 5) / 8)` and
+/// `i = 2ab^2`, a square root of -1, the root is `ab(i - 1)`.
+fn sqrt_atkin(ctx: &MontgomeryContext, a: &BigUint) -> BigUint {
+    let p = ctx.modulus();
+    let two_a = (a << 1) % p;
+    let b = ctx.modpow(&two_a, &(p >> 3));
+
+    let b_m = ctx.to_montgomery(&b);
+    let i = ctx.mul(&ctx.to_montgomery(&two_a), &ctx.mul(&b_m, &b_m));
+    let i = ctx.from_montgomery(&i);
+    // i - 1 is never negative: i = 0 would mean a = 0.
+    let ab = ctx.mul(&ctx.to_montgomery(a), &b_m);
+    ctx.from_montgomery(&ctx.mul(&ab, &ctx.to_montgomery(&(i - 1u32))))
+}
+
+/// Tonelli–Shanks for `p - 1 = q * 2^s` with odd `q`. Returns `None` if it
+/// finds that `p` cannot be prime.
+fn sqrt_tonelli_shanks(ctx: &MontgomeryContext, a: &BigUint, s: usize) -> Option<BigUint> {
+    let p = ctx.modulus();
+    let q = p >> s;
+
+    // Any quadratic non-residue z gives a generator z^q of the 2-Sylow subgroup.
+    let p_int = BigInt::from_biguint(crate::Sign::Plus, p.clone());
+    let mut z = BigUint::from(2u32);
+    loop {
+        match jacobi(&BigInt::from_biguint(crate::Sign::Plus, z.clone()), &p_int) {
+            -1 => break,
+            0 => return None,
+            _ => z += 1u32,
+        }
+    }
+
+    let one = ctx.to_montgomery(&BigUint::one());
+    let mut c = ctx.to_montgomery(&ctx.modpow(&z, &q));
+
+    // x = a^((q + 1) / 2) and t = a^q, from a single exponentiation.
+    let w = ctx.to_montgomery(&ctx.modpow(a, &(&q >> 1)));
+    let mut x = ctx.mul(&ctx.to_montgomery(a), &w);
+    let mut t = ctx.mul(&x, &w);
+    let mut m = s;
+
+    // Invariant: x^2 = a * t, and t has order dividing 2^(m - 1).
+    while t != one {
+        let mut i = 0;
+        let mut tt = t.clone();
+        while tt != one {
+            tt = ctx.mul(&tt, &tt);
+            i += 1;
+            if i == m {
+                return None;
+            }
+        }
+
+        let mut b = c;
+        for _ in 0..m - i - 1 {
+            b = ctx.mul(&b, &b);
+        }
+        m = i;
+        c = ctx.mul(&b, &b);
+        t = ctx.mul(&t, &c);
+        x = ctx.mul(&x, &b);
+    }
+
+    Some(ctx.from_montgomery(&x))
+}
+
+/// Cipolla's algorithm: for `t` with `w = t^2 - a` a non-residue,
+/// `(t + sqrt(w))^((p + 1) / 2)` in `F_p(sqrt(w))` is a root of `a`. Returns
+/// `None` if it finds that `p` cannot be prime.
+fn sqrt_cipolla(ctx: &MontgomeryContext, a: &BigUint) -> Option<BigUint> {
+    let p = ctx.modulus();
+    let p_int = BigInt::from_biguint(crate::Sign::Plus, p.clone());
+
+    let mut t = BigUint::one();
+    let w = loop {
+        let w = (&t * &t + p - a) % p;
+        if w.is_zero() {
+            return Some(t);
+        }
+        match jacobi(&BigInt::from_biguint(crate::Sign::Plus, w.clone()), &p_int) {
+            -1 => break w,
+            0 => return None,
+            _ => t += 1u32,
+        }
+    };
+
+    let add = |x: &BigUint, y: &BigUint| {
+        let z = x + y;
+        if &z >= p {
+            z - p
+        } else {
+            z
+        }
+    };
+
+    let t = ctx.to_montgomery(&t);
+    let w = ctx.to_montgomery(&w);
+
+    // (u + v * sqrt(w)), starting with t + sqrt(w) for the top bit of the exponent.
+    let e: BigUint = (p >> 1) + 1u32;
+    let mut u = t.clone();
+    let mut v = ctx.to_montgomery(&BigUint::one());
+    for i in (0..e.bits() - 1).rev() {
+        let uu = ctx.mul(&u, &u);
+        let vv = ctx.mul(&ctx.mul(&v, &v), &w);
+        let uv = ctx.mul(&u, &v);
+        u = add(&uu, &vv);
+        v = add(&uv, &uv);
+
+        if (&e >> i).is_odd() {
+            let ut = ctx.mul(&u, &t);
+            let vw = ctx.mul(&v, &w);
+            let vt = ctx.mul(&v, &t);
+            v = add(&u, &vt);
+            u = add(&ut, &vw);
+        }
+    }
+
+    Some(ctx.from_montgomery(&u))
+}
+
+/// Calculate all square roots of `a` modulo `p^k`, for a prime `p`, in increasing order.
+///
+/// Roots modulo `p` are lifted to `p^k` with Newton's iteration (Hensel's lemma), which
+/// doubles the precision in each step. For `p = 2` the roots are lifted one bit at a
+/// time, and an odd `a` has one root modulo 2, two modulo 4 if `a ≡ 1 mod 4`, and four
+/// modulo `2^k`, `k >= 3`, if `a ≡ 1 mod 8`. If `p` divides `a`, every root is a
+/// multiple of `p^(v/2)`, where `p^v` is the largest power of `p` dividing `a`; note
+/// that there are `p^(k/2)` roots of zero.
+///
+/// `p` is not checked for primality.
+///
+/// Panics if `p` is neither an odd number greater than one nor 2.
+pub fn sqrt_mod_prime_power(a: &BigUint, p: &BigUint, k: u32) -> Vec<BigUint> {
+    let two = BigUint::from(2u32);
+    assert!(
+        (p.is_odd() && !p.is_one()) || p == &two,
+        "the modulus must be an odd prime or 2"
+    );
+
+    let pk = p.pow(k);
+    let mut b = a % &pk;
+
+    // x^2 = 0 mod p^k if and only if p^ceil(k/2) divides x.
+    if b.is_zero() {
+        return multiples(&p.pow(Integer::div_ceil(&k, &2)), &BigUint::zero(), &pk);
+    }
+
+    let mut v = 0;
+    while (&b % p).is_zero() {
+        b /= p;
+        v += 1;
+    }
+    if v % 2 == 1 {
+        return Vec::new();
+    }
+
+    // a = p^v * b, so x = p^(v/2) * y with y^2 = b mod p^(k - v), which fixes y only
+    // modulo p^(k - v) but x modulo p^k.
+    let e = k - v;
+    let roots = if p == &two {
+        sqrt_mod_2k(&b, e)
+    } else {
+        sqrt_mod_odd_prime_power(&b, p, e)
+    };
+
+    let h = p.pow(v / 2);
+    let pe = p.pow(e);
+    let bound = p.pow(k - v / 2);
+    let mut res = Vec::with_capacity(roots.len());
+    for y in &roots {
+        res.extend(multiples(&pe, y, &bound).iter().map(|y| &h * y));
+    }
+    res.sort();
+    res
+}
+
+/// Calculate all square roots of `a` modulo `n = p_1^k_1 * ... * p_m^k_m`, given the
+/// factorization of `n` as pairs `(p_i, k_i)` of distinct primes and exponents, in
+/// increasing order.
+///
+/// The roots modulo each prime power come from `sqrt_mod_prime_power`, and every
+/// combination of them is joined with the Chinese remainder theorem. For an RSA-style
+/// modulus `n = pq` and `a` coprime to `n` this gives the four roots used by Rabin
+/// decryption.
+///
+/// Panics if a prime appears twice or is neither odd nor 2.
+pub fn sqrt_mod_factored(a: &BigUint, factors: &[(BigUint, u32)]) -> Vec<BigUint> {
+    let moduli: Vec<BigUint> = factors.iter().map(|&(ref p, k)| p.pow(k)).collect();
+    let n = moduli.iter().product::<BigUint>();
+
+    let mut res = vec![BigUint::zero()];
+    for (&(ref p, k), m) in factors.iter().zip(&moduli) {
+        // c = 1 mod m and c = 0 modulo the other prime powers.
+        let rest = &n / m;
+        let inv = (&rest % m)
+            .mod_inverse(m)
+            .expect("the primes must be distinct")
+            .into_biguint()
+            .unwrap();
+        let c = rest * inv;
+
+        let roots = sqrt_mod_prime_power(a, p, k);
+        if roots.is_empty() {
+            return roots;
+        }
+
+        let (c, n) = (&c, &n);
+        res = res
+            .iter()
+            .flat_map(|x| roots.iter().map(move |r| (x + r * c) % n))
+            .collect();
+    }
+    res.sort();
+    res
+}
+
+/// All `start + i * step` below `bound`.
+fn multiples(step: &BigUint, start: &BigUint, bound: &BigUint) -> Vec<BigUint> {
+    let mut res = Vec::new();
+    let mut x = start.clone();
+    while &x < bound {
+        res.push(x.clone());
+        x += step;
+    }
+    res
+}
+
+/// The roots of `b` modulo `p^e` for an odd prime `p` and `b` coprime to `p`.
+fn sqrt_mod_odd_prime_power(b: &BigUint, p: &BigUint, e: u32) -> Vec<BigUint> {
+    let mut r = match sqrt_mod_prime(b, p) {
+        Some(r) => r,
+        None => return Vec::new(),
+    };
+
+    // r^2 = b mod p^i implies that r - (r^2 - b) / 2r is a root modulo p^(2i).
+    let mut i = 1;
+    while i < e {
+        i = ::std::cmp::min(2 * i, e);
+        let pi = p.pow(i);
+        let inv = (&r << 1)
+            .mod_inverse(&pi)
+            .expect("the modulus must be prime")
+            .into_biguint()
+            .unwrap();
+        let diff = ((&r * &r) + &pi - b % &pi) % &pi;
+        r = (r + &pi - diff * inv % &pi) % &pi;
+    }
+
+    let neg = p.pow(e) - &r;
+    vec![r, neg]
+}
+
+/// The roots of an odd `b` modulo `2^e`.
+fn sqrt_mod_2k(b: &BigUint, e: u32) -> Vec<BigUint> {
+    let low = b.data[0] & 7;
+    match e {
+        1 => return vec![BigUint::one()],
+        2 if low & 3 == 1 => return vec![BigUint::from(1u32), BigUint::from(3u32)],
+        _ if low != 1 => return Vec::new(),
+        _ => {}
+    }
+
+    // If r^2 = b mod 2^i but not mod 2^(i + 1), then (r + 2^(i - 1))^2 is, for i >= 3.
+    let mut r = BigUint::one();
+    for i in 3..e as usize {
+        if ((&r * &r) >> i).is_odd() != (b >> i).is_odd() {
+            r += BigUint::one() << (i - 1);
+        }
+    }
+
+    let m = BigUint::one() << e as usize;
+    let half = BigUint::one() << (e as usize - 1);
+    let s = (&r + &half) % &m;
+    let mut res = vec![&m - &r, &m - &s, r, s];
+    res.sort();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use num_traits::Num;
+
+    fn check(a: &BigUint, p: &BigUint) {
+        let r = sqrt_mod_prime(a, p);
+        let a_int = BigInt::from_biguint(crate::Sign::Plus, a % p);
+        let p_int = BigInt::from_biguint(crate::Sign::Plus, p.clone());
+        match r {
+            Some(r) => {
+                assert!(
+                    &r + &r <= *p,
+                    "sqrt({}) mod {} = {} is not the smaller root",
+                    a,
+                    p,
+                    r
+                );
+                assert_eq!((&r * &r) % p, a % p, "sqrt({}) mod {} = {}", a, p, r);
+            }
+            None => assert_eq!(jacobi(&a_int, &p_int), -1, "sqrt({}) mod {}", a, p),
+        }
+    }
+
+    #[test]
+    fn test_sqrt_mod_small_primes() {
+        for p in 2u32..600 {
+            if (2..p).take_while(|d| d * d <= p).any(|d| p % d == 0) {
+                continue;
+            }
+            let p = BigUint::from(p);
+            for a in 0u32..600 {
+                check(&BigUint::from(a), &p);
+            }
+        }
+
+        // s = 16, which uses Cipolla.
+        let p = BigUint::from(65537u32);
+        for a in (0u32..65537).step_by(97) {
+            check(&BigUint::from(a), &p);
+        }
+    }
+
+    #[test]
+    fn test_sqrt_mod_large_primes() {
+        let primes = [
+            // 2^127 - 1, p = 3 mod 4
+            "7fffffffffffffffffffffffffffffff",
+            // 2^255 - 19, p = 5 mod 8
+            "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed",
+            // BLS12-381 group order, s = 32, Tonelli–Shanks
+            "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001",
+            // 2^64 - 2^32 + 1, s = 32, Cipolla
+            "ffffffff00000001",
+            // NIST P-224, s = 96, Cipolla
+            "ffffffffffffffffffffffffffffffff000000000000000000000001",
+        ];
+
+        for p in &primes {
+            let p = BigUint::from_str_radix(p, 16).unwrap();
+            let mut a =
+                BigUint::from_str_radix("123456789abcdef0fedcba9876543210", 16).unwrap() % &p;
+            for _ in 0..20 {
+                check(&a, &p);
+                let sq = (&a * &a) % &p;
+                let r = sqrt_mod_prime(&sq, &p).unwrap();
+                assert!(r == a || r == &p - &a);
+                a = (&a * &a + 12345u32) % &p;
+            }
+        }
+    }
+
+    #[test]
+    fn test_sqrt_mod_composite() {
+        // Roots that exist are found, the others are not.
+        for &n in &[3u32 * 5 * 17, 3 * 11, 5 * 13, 7 * 7, 17 * 17, 3 * 3 * 3 * 3] {
+            let n = BigUint::from(n);
+            for a in 0u32..300 {
+                if let Some(r) = sqrt_mod_prime(&BigUint::from(a), &n) {
+                    assert_eq!((&r * &r) % &n, BigUint::from(a) % &n);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the modulus must be an odd prime or 2")]
+    fn test_sqrt_mod_even() {
+        let _ = sqrt_mod_prime(&BigUint::from(4u32), &BigUint::from(12u32));
+    }
+
+    fn brute_force(a: u32, n: u32) -> Vec<BigUint> {
+        (0..n)
+            .filter(|x| (x * x) % n == a % n)
+            .map(BigUint::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_sqrt_mod_prime_power() {
+        for &(p, max_k) in &[(2u32, 11), (3, 7), (5, 5), (7, 4), (11, 3), (13, 3)] {
+            for k in 0..=max_k {
+                let n = p.pow(k);
+                for a in 0..n + 3 {
+                    assert_eq!(
+                        sqrt_mod_prime_power(&BigUint::from(a), &BigUint::from(p), k),
+                        brute_force(a, n),
+                        "sqrt({}) mod {}^{}",
+                        a,
+                        p,
+                        k
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sqrt_mod_prime_power_large() {
+        let p = BigUint::from_str_radix(
+            "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed",
+            16,
+        )
+        .unwrap();
+        let two = BigUint::from(2u32);
+        // Multiples of p have p^(v/2) times as many roots, so only shift them for p = 2.
+        for &(ref p, k, max_shift) in &[(p, 5, 0u32), (two, 300, 3)] {
+            let pk = p.pow(k);
+            let mut x = BigUint::from_str_radix("123456789abcdef0fedcba9876543211", 16).unwrap();
+            for _ in 0..10 {
+                for shift in 0..=max_shift {
+                    let y = &x * p.pow(shift) % &pk;
+                    let a = &y * &y % &pk;
+                    let roots = sqrt_mod_prime_power(&a, p, k);
+                    assert!(roots.contains(&y));
+                    for r in &roots {
+                        assert_eq!(r * r % &pk, a);
+                    }
+                }
+                x = (&x * &x + 3u32) % &pk;
+            }
+        }
+    }
+
+    #[test]
+    fn test_sqrt_mod_factored() {
+        let cases: &[&[(u32, u32)]] = &[
+            &[],
+            &[(3, 1), (5, 1)],
+            &[(2, 3), (3, 2), (5, 1)],
+            &[(2, 2), (7, 1), (11, 1)],
+            &[(13, 2), (2, 1)],
+        ];
+        for factors in cases {
+            let n: u32 = factors.iter().map(|&(p, k)| p.pow(k)).product();
+            let factors: Vec<(BigUint, u32)> = factors
+                .iter()
+                .map(|&(p, k)| (BigUint::from(p), k))
+                .collect();
+            for a in 0..n {
+                assert_eq!(
+                    sqrt_mod_factored(&BigUint::from(a), &factors),
+                    brute_force(a, n)
+                );
+            }
+        }
+
+        // Rabin: four roots modulo pq.
+        let p = BigUint::from_str_radix("7fffffffffffffffffffffffffffffff", 16).unwrap();
+        let q = BigUint::from_str_radix("1fffffffffffffff", 16).unwrap();
+        let n = &p * &q;
+        let x = BigUint::from_str_radix("123456789abcdef0fedcba9876543211", 16).unwrap();
+        let roots = sqrt_mod_factored(&(&x * &x % &n), &[(p, 1), (q, 1)]);
+        assert_eq!(roots.len(), 4);
+        assert!(roots.contains(&x));
+        assert!(roots.contains(&(&n - &x)));
+    }
+
+    #[test]
+    #[should_panic(expected = "the primes must be distinct")]
+    fn test_sqrt_mod_factored_repeated() {
+        let three = BigUint::from(3u32);
+        let _ = sqrt_mod_factored(&BigUint::one(), &[(three.clone(), 1), (three, 2)]);
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The operand sizes (in digits) at which multiplication, squaring, division and radix
+/// conversion switch between algorithms.
+///
+/// The defaults for 64 bit digits are the cutoffs the algorithms were written with. Those for
+/// 32 bit digits are scaled up by hand and have not been measured. As the best values depend
+/// on the CPU, they can be replaced at runtime, usually once at startup:
+///
+/// ```
+/// extern crate num_bigint_dig as num_bigint;
+///
+/// use num_bigint::algorithms::Thresholds;
+///
+/// # fn main() {
+/// let mut thresholds = Thresholds::get();
+/// thresholds.mul_karatsuba = 40;
+/// thresholds.set();
+///
+/// assert_eq!(Thresholds::get().mul_karatsuba, 40);
+/// # Thresholds::default().set();
+/// # }
+/// ```
+///
+/// The thresholds are global to the process and only affect performance, never results. Each
+/// algorithm is used from its threshold on, unless a later one in the same field order applies
+/// as well, so setting a threshold to `usize::MAX` disables the algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Thresholds {
+    /// Length of the smaller factor from which on Karatsuba multiplication is used instead of
+    /// long multiplication.
+    pub mul_karatsuba: usize,
+
+    /// Length of the smaller factor from which on Toom-3 multiplication is used.
+    pub mul_toom3: usize,
+
+    /// Length of the smaller factor from which on NTT multiplication is used.
+    pub mul_ntt: usize,
+
+    /// Length from which on squaring only computes half of the partial products, instead of
+    /// doing a long multiplication.
+    pub sqr_long: usize,
+
+    /// Length from which on Karatsuba squaring is used.
+    pub sqr_karatsuba: usize,
+
+    /// Length from which on Toom-3 squaring is used.
+    pub sqr_toom3: usize,
+
+    /// Length from which on NTT squaring is used.
+    pub sqr_ntt: usize,
+
+    /// Length of the modulus from which on the squarings of `modpow` are followed by a separate
+    /// Montgomery reduction, instead of an interleaved Montgomery multiplication.
+    pub montgomery_sqr: usize,
+
+    /// Length of the divisor (and of the quotient) from which on the recursive Burnikel-Ziegler
+    /// division is used instead of schoolbook division.
+    pub div_burnikel_ziegler: usize,
+
+    /// Length of the smaller operand from which on `gcd` uses the recursive half-GCD, instead of
+    /// only Lehmer's algorithm.
+    pub gcd_half_gcd: usize,
+
+    /// Length of the smaller operand from which on `extended_gcd` uses the recursive half-GCD.
+    /// This is also the size below which the half-GCD recursion stops.
+    pub extended_gcd_half_gcd: usize,
+
+    /// Length above which conversions from and to a radix that is not a power of two use
+    /// divide and conquer, instead of multiplying or dividing by one digit at a time.
+    pub radix_conversion: usize,
+}
+
+#[cfg(feature = "u64_digit")]
+const DEFAULT: Thresholds = Thresholds {
+    mul_karatsuba: 32,
+    mul_toom3: 256,
+    mul_ntt: 4096,
+    sqr_long: 16,
+    sqr_karatsuba: 32,
+    sqr_toom3: 256,
+    sqr_ntt: 4096,
+    montgomery_sqr: 32,
+    div_burnikel_ziegler: 64,
+    gcd_half_gcd: 256,
+    extended_gcd_half_gcd: 64,
+    radix_conversion: 64,
+};
+
+#[cfg(not(feature = "u64_digit"))]
+const DEFAULT: Thresholds = Thresholds {
+    mul_karatsuba: 40,
+    mul_toom3: 512,
+    mul_ntt: 8192,
+    sqr_long: 16,
+    sqr_karatsuba: 64,
+    sqr_toom3: 768,
+    sqr_ntt: 8192,
+    montgomery_sqr: 64,
+    div_burnikel_ziegler: 128,
+    gcd_half_gcd: 512,
+    extended_gcd_half_gcd: 128,
+    radix_conversion: 128,
+};
+
+static MUL_KARATSUBA: AtomicUsize = AtomicUsize::new(DEFAULT.mul_karatsuba);
+static MUL_TOOM3: AtomicUsize = AtomicUsize::new(DEFAULT.mul_toom3);
+static MUL_NTT: AtomicUsize = AtomicUsize::new(DEFAULT.mul_ntt);
+static SQR_LONG: AtomicUsize = AtomicUsize::new(DEFAULT.sqr_long);
+static SQR_KARATSUBA: AtomicUsize = AtomicUsize::new(DEFAULT.sqr_karatsuba);
+static SQR_TOOM3: AtomicUsize = AtomicUsize::new(DEFAULT.sqr_toom3);
+static SQR_NTT: AtomicUsize = AtomicUsize::new(DEFAULT.sqr_ntt);
+static MONTGOMERY_SQR: AtomicUsize = AtomicUsize::new(DEFAULT.montgomery_sqr);
+static DIV_BURNIKEL_ZIEGLER: AtomicUsize = AtomicUsize::new(DEFAULT.div_burnikel_ziegler);
+static GCD_HALF_GCD: AtomicUsize = AtomicUsize::new(DEFAULT.gcd_half_gcd);
+static EXTENDED_GCD_HALF_GCD: AtomicUsize = AtomicUsize::new(DEFAULT.extended_gcd_half_gcd);
+static RADIX_CONVERSION: AtomicUsize = AtomicUsize::new(DEFAULT.radix_conversion);
+
+impl Default for Thresholds {
+    /// Returns the built-in thresholds for the digit size of this build.
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+impl Thresholds {
+    /// Returns the thresholds currently in use.
+    pub fn get() -> Self {
+        Thresholds {
+            mul_karatsuba: MUL_KARATSUBA.load(Ordering::Relaxed),
+            mul_toom3: MUL_TOOM3.load(Ordering::Relaxed),
+            mul_ntt: MUL_NTT.load(Ordering::Relaxed),
+            sqr_long: SQR_LONG.load(Ordering::Relaxed),
+            sqr_karatsuba: SQR_KARATSUBA.load(Ordering::Relaxed),
+            sqr_toom3: SQR_TOOM3.load(Ordering::Relaxed),
+            sqr_ntt: SQR_NTT.load(Ordering::Relaxed),
+            montgomery_sqr: MONTGOMERY_SQR.load(Ordering::Relaxed),
+            div_burnikel_ziegler: DIV_BURNIKEL_ZIEGLER.load(Ordering::Relaxed),
+            gcd_half_gcd: GCD_HALF_GCD.load(Ordering::Relaxed),
+            extended_gcd_half_gcd: EXTENDED_GCD_HALF_GCD.load(Ordering::Relaxed),
+            radix_conversion: RADIX_CONVERSION.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Makes all following operations (on any thread) use these thresholds.
+    ///
+    /// Operations running concurrently may see a mix of the old and the new values.
+    ///
+    /// Panics if any threshold of a recursive algorithm (all but `sqr_long` and `montgomery_sqr`)
+    /// is less than 2, as those have to split their operands.
+    pub fn set(&self) {
+        assert!(self.mul_karatsuba >= 2 && self.mul_toom3 >= 2 && self.mul_ntt >= 2);
+        assert!(self.sqr_karatsuba >= 2 && self.sqr_toom3 >= 2 && self.sqr_ntt >= 2);
+        assert!(self.div_burnikel_ziegler >= 2 && self.radix_conversion >= 2);
+        assert!(self.gcd_half_gcd >= 2 && self.extended_gcd_half_gcd >= 2);
+
+        MUL_KARATSUBA.store(self.mul_karatsuba, Ordering::Relaxed);
+        MUL_TOOM3.store(self.mul_toom3, Ordering::Relaxed);
+        MUL_NTT.store(self.mul_ntt, Ordering::Relaxed);
+        SQR_LONG.store(self.sqr_long, Ordering::Relaxed);
+        SQR_KARATSUBA.store(self.sqr_karatsuba, Ordering::Relaxed);
+        SQR_TOOM3.store(self.sqr_toom3, Ordering::Relaxed);
+        SQR_NTT.store(self.sqr_ntt, Ordering::Relaxed);
+        MONTGOMERY_SQR.store(self.montgomery_sqr, Ordering::Relaxed);
+        DIV_BURNIKEL_ZIEGLER.store(self.div_burnikel_ziegler, Ordering::Relaxed);
+        GCD_HALF_GCD.store(self.gcd_half_gcd, Ordering::Relaxed);
+        EXTENDED_GCD_HALF_GCD.store(self.extended_gcd_half_gcd, Ordering::Relaxed);
+        RADIX_CONVERSION.store(self.radix_conversion, Ordering::Relaxed);
+    }
+}
+
+// Single threshold accessors for the hot paths, which don't need all of them.
+
+#[inline]
+pub(crate) fn mul_karatsuba_threshold() -> usize {
+    MUL_KARATSUBA.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn mul_toom3_threshold() -> usize {
+    MUL_TOOM3.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn mul_ntt_threshold() -> usize {
+    MUL_NTT.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn sqr_long_threshold() -> usize {
+    SQR_LONG.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn sqr_karatsuba_threshold() -> usize {
+    SQR_KARATSUBA.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn sqr_toom3_threshold() -> usize {
+    SQR_TOOM3.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn sqr_ntt_threshold() -> usize {
+    SQR_NTT.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn montgomery_sqr_threshold() -> usize {
+    MONTGOMERY_SQR.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn burnikel_ziegler_threshold() -> usize {
+    DIV_BURNIKEL_ZIEGLER.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn half_gcd_threshold() -> usize {
+    GCD_HALF_GCD.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn extended_half_gcd_threshold() -> usize {
+    EXTENDED_GCD_HALF_GCD.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn radix_conversion_threshold() -> usize {
+    RADIX_CONVERSION.load(Ordering::Relaxed)
+}
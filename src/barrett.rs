@@ -0,0 +1,235 @@
+use integer::Integer;
+use num_traits::{One, Zero};
+use smallvec::SmallVec;
+use std::cmp;
+
+use super::monty::{bit, window_size};
+use algorithms::{adc, mac_digit, mac_with_carry, mul3, mul_karatsuba_threshold, sbb};
+use big_digit::{self, BigDigit};
+use biguint::BigUint;
+use VEC_SIZE;
+
+/// A Barrett reduction context for a fixed, nonzero modulus.
+///
+/// Building the context computes `mu = floor(4^k / m)` once, where `k` is the
+/// bit length of `m` rounded up to whole digits. Each reduction of a number
+/// below `4^k` then takes two multiplications and at most two subtractions
+/// instead of a long division. Unlike `MontgomeryContext` this works for even
+/// moduli, and values are kept in regular form.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint_dig::{BarrettReducer, BigUint};
+///
+/// let m = BigUint::from(1_000_000_000_000u64);
+/// let ctx = BarrettReducer::new(&m);
+///
+/// let a = BigUint::from(123_456_789_012u64);
+/// let b = BigUint::from(987_654_321_098u64);
+/// assert_eq!(ctx.mul_mod(&a, &b), &a * &b % &m);
+/// assert_eq!(ctx.reduce(&(&a << 200)), (&a << 200) % &m);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BarrettReducer {
+    modulus: BigUint,
+    // mu = floor(B^(2 * len(m)) / m), where B = 2^BITS
+    mu: BigUint,
+    num_words: usize,
+}
+
+impl BarrettReducer {
+    /// Creates a new context for the given modulus.
+    ///
+    /// Panics if the modulus is zero.
+    pub fn new(modulus: &BigUint) -> Self {
+        assert!(!modulus.is_zero(), "divide by zero!");
+        let num_words = modulus.data.len();
+
+        let mu = (BigUint::one() << (2 * num_words * big_digit::BITS)) / modulus;
+
+        BarrettReducer {
+            modulus: modulus.clone(),
+            mu,
+            num_words,
+        }
+    }
+
+    /// Returns the modulus of this context.
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    /// Returns `x mod m`, for any `x`.
+    ///
+    /// Numbers with more than twice as many digits as the modulus are reduced
+    /// from the top, one modulus length at a time.
+    pub fn reduce(&self, x: &BigUint) -> BigUint {
+        let k = self.num_words;
+        if x.data.len() <= 2 * k {
+            return self.reduce_short(x);
+        }
+
+        // r < m, so r * B^k + chunk < m * B^k <= B^(2k)
+        let chunks = Integer::div_ceil(&x.data.len(), &k);
+        let mut r = BigUint::zero();
+        for i in (0..chunks).rev() {
+            let lo = i * k;
+            let hi = cmp::min(lo + k, x.data.len());
+
+            let mut t = r << (k * big_digit::BITS);
+            t += BigUint::from_slice_native(&x.data[lo..hi]);
+            r = self.reduce_short(&t);
+        }
+        r
+    }
+
+    /// Returns `a * b mod m`.
+    pub fn mul_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.reduce(&(a * b))
+    }
+
+    /// Returns `a * a mod m`.
+    pub fn sqr_mod(&self, a: &BigUint) -> BigUint {
+        self.reduce(&(a * a))
+    }
+
+    /// Returns `x ** y mod m`.
+    ///
+    /// This uses the same sliding window as `MontgomeryContext::modpow`, with
+    /// Barrett reductions, so it also works for even moduli.
+    pub fn modpow(&self, x: &BigUint, y: &BigUint) -> BigUint {
+        if y.is_zero() {
+            return self.reduce(&BigUint::one());
+        }
+
+        let bits = y.bits();
+        let w = window_size(bits);
+
+        // powers[i] contains x^(2*i + 1)
+        let mut powers = Vec::with_capacity(1 << (w - 1));
+        powers.push(self.reduce(x));
+        if w > 1 {
+            let x2 = self.sqr_mod(&powers[0]);
+            for i in 1..1 << (w - 1) {
+                let next = self.mul_mod(&powers[i - 1], &x2);
+                powers.push(next);
+            }
+        }
+
+        let mut z = BigUint::zero();
+        let mut started = false;
+
+        // i is the number of exponent bits not consumed yet
+        let mut i = bits;
+        while i > 0 {
+            if !bit(y, i - 1) {
+                z = self.sqr_mod(&z);
+                i -= 1;
+                continue;
+            }
+
+            // The longest window of at most w bits, starting at bit i - 1 and
+            // ending in a one bit.
+            let mut l = i.saturating_sub(w);
+            while !bit(y, l) {
+                l += 1;
+            }
+            let mut window = 0;
+            for j in (l..i).rev() {
+                window = (window << 1) | bit(y, j) as usize;
+            }
+
+            if started {
+                for _ in l..i {
+                    z = self.sqr_mod(&z);
+                }
+                z = self.mul_mod(&z, &powers[window >> 1]);
+            } else {
+                z = powers[window >> 1].clone();
+                started = true;
+            }
+            i = l;
+        }
+
+        z
+    }
+
+    /// Barrett reduction (Menezes et al., Handbook of Applied Cryptography,
+    /// algorithm 14.42) of `x < B^(2k)`, where `k = len(m)`.
+    fn reduce_short(&self, x: &BigUint) -> BigUint {
+        let k = self.num_words;
+        let m = &self.modulus;
+        debug_assert!(x.data.len() <= 2 * k);
+
+        if x < m {
+            return x.clone();
+        }
+
+        // q = floor(floor(x / B^(k-1)) * mu / B^(k+1)) is at most two less than
+        // floor(x / m), and never more.
+        let q1 = &x.data[k - 1..];
+        let mut r = if k < mul_karatsuba_threshold() {
+            self.remainder_long(x, q1)
+        } else {
+            let q2 = mul3(q1, &self.mu.data);
+            let q = BigUint::from_slice_native(q2.data.get(k + 1..).unwrap_or(&[]));
+            x - q * m
+        };
+
+        while &r >= m {
+            r -= m;
+        }
+        r
+    }
+
+    /// Returns x - q * m, like `reduce_short`, but only computes the parts of
+    /// the products that are needed, with long multiplications (HAC note 14.44).
+    fn remainder_long(&self, x: &BigUint, q1: &[BigDigit]) -> BigUint {
+        let k = self.num_words;
+        let mu = &self.mu.data;
+
+        // Only the digits of q1 * mu from k - 1 on: the carries from the lower
+        // ones can't make q more than one larger.
+        let mut q2: SmallVec<[BigDigit; VEC_SIZE]> = smallvec![0; q1.len() + mu.len() + 1];
+        for (i, &qi) in q1.iter().enumerate() {
+            let start = (k - 1).saturating_sub(i);
+            if start < mu.len() {
+                mac_digit(&mut q2[i + start..], &mu[start..], qi);
+            }
+        }
+        let q = &q2[k + 1..];
+
+        // The remainder is less than 3m < B^(k+1), so it suffices to compute it
+        // modulo B^(k+1), from the low k + 1 digits of x and q * m.
+        let mut qm: SmallVec<[BigDigit; VEC_SIZE]> = smallvec![0; k + 1];
+        for (i, &qi) in q.iter().enumerate().take(k + 1) {
+            mac_digit_truncated(&mut qm[i..], &self.modulus.data, qi);
+        }
+
+        let mut r = BigUint::from_slice_native(&x.data[..cmp::min(k + 1, x.data.len())]);
+        r.data.resize(k + 1, 0);
+        let mut borrow = 0;
+        for (ri, &qmi) in r.data.iter_mut().zip(qm.iter()) {
+            *ri = sbb(*ri, qmi, &mut borrow);
+        }
+        r.normalized()
+    }
+}
+
+/// acc += b * c, modulo B^acc.len().
+fn mac_digit_truncated(acc: &mut [BigDigit], b: &[BigDigit], c: BigDigit) {
+    let mut carry = 0;
+    let len = cmp::min(acc.len(), b.len());
+    let (a_lo, a_hi) = acc.split_at_mut(len);
+
+    for (a, &b) in a_lo.iter_mut().zip(b) {
+        *a = mac_with_carry(*a, b, c, &mut carry);
+    }
+    for a in a_hi {
+        if carry == 0 {
+            break;
+        }
+        *a = adc(*a, 0, &mut carry);
+    }
+}
@@ -10,7 +10,7 @@ use std::ops::{
     Mul, MulAssign, Neg, Not, Rem, RemAssign, Shl, ShlAssign, Shr, ShrAssign, Sub, SubAssign,
 };
 use std::str::{self, FromStr};
-use std::{fmt, mem};
+use std::{cmp, fmt, mem};
 #[cfg(has_i128)]
 use std::{i128, u128};
 use std::{i64, u64};
@@ -39,7 +39,7 @@ use smallvec::SmallVec;
 use IsizePromotion;
 use UsizePromotion;
 
-use crate::algorithms::{extended_gcd, mod_inverse};
+use crate::algorithms::{extended_gcd, mod_inverse, msc3, negate_digits};
 use crate::biguint::IntoBigUint;
 use crate::traits::{ExtendedGcd, ModInverse};
 
@@ -3146,6 +3146,80 @@ impl BigInt {
         Roots::nth_root(self, n)
     }
 
+    /// Adds `b * c` to `self`, in place.
+    ///
+    /// Like `BigUint::add_mul`, the product is accumulated directly into the digits of `self`.
+    pub fn add_mul(&mut self, b: &BigInt, c: &BigInt) {
+        self.mul_acc(b, c, b.sign * c.sign);
+    }
+
+    /// Subtracts `b * c` from `self`, in place.
+    pub fn sub_mul(&mut self, b: &BigInt, c: &BigInt) {
+        self.mul_acc(b, c, -(b.sign * c.sign));
+    }
+
+    /// Adds `|b * c|` with the given sign to `self`.
+    fn mul_acc(&mut self, b: &BigInt, c: &BigInt, sign: Sign) {
+        if sign == NoSign {
+            return;
+        }
+        if self.sign == NoSign || self.sign == sign {
+            self.data.add_mul(&b.data, &c.data);
+            self.sign = sign;
+            return;
+        }
+
+        // Opposite signs: subtract the magnitudes, and if that wraps around, the product was
+        // larger and the result has its sign.
+        let len = cmp::max(self.data.data.len(), b.data.data.len() + c.data.data.len());
+        self.data.data.resize(len, 0);
+        if msc3(&mut self.data.data[..], &b.data.data[..], &c.data.data[..]) {
+            negate_digits(&mut self.data.data[..]);
+            self.sign = sign;
+        }
+        self.data.normalize();
+        if self.data.is_zero() {
+            self.sign = NoSign;
+        }
+    }
+
+    /// Returns the sum of the products of all pairs, `b0 * c0 + b1 * c1 + ...`.
+    ///
+    /// The positive and the negative products are accumulated into one buffer each, which are
+    /// only normalized and combined at the end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use num_bigint_dig::BigInt;
+    ///
+    /// let b = [BigInt::from(2), BigInt::from(-3)];
+    /// let c = [BigInt::from(5), BigInt::from(7)];
+    /// assert_eq!(BigInt::dot_product(b.iter().zip(&c)), BigInt::from(-11));
+    /// ```
+    pub fn dot_product<'a, I>(pairs: I) -> BigInt
+    where
+        I: IntoIterator<Item = (&'a BigInt, &'a BigInt)>,
+    {
+        let mut pos = BigUint::zero();
+        let mut neg = BigUint::zero();
+        for (b, c) in pairs {
+            match b.sign * c.sign {
+                Plus => biguint::mac_unnormalized(&mut pos, &b.data, &c.data),
+                Minus => biguint::mac_unnormalized(&mut neg, &b.data, &c.data),
+                NoSign => {}
+            }
+        }
+        pos.normalize();
+        neg.normalize();
+
+        match pos.cmp(&neg) {
+            Greater => BigInt::from_biguint(Plus, pos - neg),
+            Less => BigInt::from_biguint(Minus, neg - pos),
+            Equal => BigInt::zero(),
+        }
+    }
+
     pub fn get_limb(&self, n: usize) -> BigDigit {
         self.data.get_limb(n)
     }
@@ -19,7 +19,7 @@ use num_traits::Zero;
 use num_traits::{FromPrimitive, ToPrimitive};
 
 #[cfg(feature = "prime")]
-use crate::prime::probably_prime;
+use crate::prime::probably_prime_with_rng;
 
 pub trait RandBigInt {
     /// Generate a random `BigUint` of the given bit size.
@@ -269,6 +269,9 @@ impl Distribution<BigInt> for RandomBits {
 /// *Warning*: This is highly dependend on the provided random number generator,
 /// to provide actually random primes.
 ///
+/// The bases of the Miller-Rabin rounds in the primality test are drawn from
+/// the same generator, see `prime::probably_prime_with_rng`.
+///
 /// # Example
 /// ```
 /// extern crate rand;
@@ -364,7 +367,7 @@ impl<R: Rng + ?Sized> RandPrime for R {
 
             // There is a tiny possibility that, by adding delta, we caused
             // the number to be one bit too long. Thus we check bit length here.
-            if p.bits() == bit_size && probably_prime(&p, 20) {
+            if p.bits() == bit_size && probably_prime_with_rng(&p, 20, self) {
                 return p;
             }
         }
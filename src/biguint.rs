@@ -34,13 +34,21 @@ use smallvec::SmallVec;
 #[path = "monty.rs"]
 mod monty;
 
-use self::monty::monty_modpow;
+#[path = "barrett.rs"]
+mod barrett;
+
+pub use self::barrett::BarrettReducer;
+pub use self::monty::{FixedBaseExp, MontgomeryContext};
 use super::VEC_SIZE;
 use crate::algorithms::{__add2, __sub2rev, add2, sub2, sub2rev};
+use crate::algorithms::{biguint_gcd, extended_gcd, mod_inverse};
 use crate::algorithms::{biguint_shl, biguint_shr};
 use crate::algorithms::{cmp_slice, fls, ilog2};
 use crate::algorithms::{div_rem, div_rem_digit, mac_with_carry, mul3, scalar_mul};
-use crate::algorithms::{extended_gcd, mod_inverse};
+use crate::algorithms::{div_rem_scratch, mac3, mac3_scratch, mac3_scratch_len, mac_sqr, msc3};
+use crate::algorithms::{mul_karatsuba_threshold, mul_ntt_threshold, radix_conversion_threshold};
+use crate::algorithms::{sqrt_mod_factored, sqrt_mod_prime, sqrt_mod_prime_power};
+use crate::scratch::Scratch;
 use crate::traits::{ExtendedGcd, ModInverse};
 
 use ParseBigIntError;
@@ -193,12 +201,16 @@ fn from_radix_digits_be(v: &[u8], radix: u32) -> BigUint {
     debug_assert!(!v.is_empty() && !radix.is_power_of_two());
     debug_assert!(v.iter().all(|&c| (c as u32) < radix));
 
+    let (base, power) = get_radix_base(radix);
+    if v.len() > radix_conversion_threshold() * power {
+        return from_radix_digits_be_large(v, radix);
+    }
+
     // Estimate how big the result will be, so we can pre-allocate it.
     let bits = (radix as f64).log2() * v.len() as f64;
     let big_digits = (bits / big_digit::BITS as f64).ceil();
     let mut data = SmallVec::with_capacity(big_digits as usize);
 
-    let (base, power) = get_radix_base(radix);
     let radix = radix as BigDigit;
 
     let r = v.len() % power;
@@ -227,6 +239,49 @@ fn from_radix_digits_be(v: &[u8], radix: u32) -> BigUint {
     BigUint::new_native(data)
 }
 
+// Returns the powers radix^(power * 2^k) for k = 0, 1, ..., up to the first one with at least
+// `digits` radix digits, where `(_, power) = get_radix_base(radix)`.
+fn radix_powers(radix: u32, digits: usize) -> Vec<BigUint> {
+    let (base, power) = get_radix_base(radix);
+
+    let mut powers = vec![BigUint::from(base)];
+    let mut n = power;
+    while n < digits {
+        let next = {
+            let last = powers.last().unwrap();
+            last * last
+        };
+        powers.push(next);
+        n *= 2;
+    }
+    powers
+}
+
+// Read big-endian radix digits by splitting them into a high and a low part, with the low part
+// having power * 2^k digits, and combining the recursively converted parts as
+// high * radix^(power * 2^k) + low.
+fn from_radix_digits_be_large(v: &[u8], radix: u32) -> BigUint {
+    let powers = radix_powers(radix, Integer::div_ceil(&v.len(), &2));
+    from_radix_digits_be_rec(v, radix, &powers)
+}
+
+fn from_radix_digits_be_rec(v: &[u8], radix: u32, powers: &[BigUint]) -> BigUint {
+    let (_, power) = get_radix_base(radix);
+    if v.len() <= radix_conversion_threshold() * power {
+        return from_radix_digits_be(v, radix);
+    }
+
+    // the largest k with power * 2^k < v.len(), so the high part is not larger than the low
+    let mut k = 0;
+    while power << (k + 1) < v.len() {
+        k += 1;
+    }
+    let (high, low) = v.split_at(v.len() - (power << k));
+
+    from_radix_digits_be_rec(high, radix, powers) * &powers[k]
+        + from_radix_digits_be_rec(low, radix, powers)
+}
+
 impl Num for BigUint {
     type FromStrRadixErr = ParseBigIntError;
 
@@ -1368,8 +1423,7 @@ impl Integer for BigUint {
     /// The result is always positive.
     #[inline]
     fn gcd(&self, other: &Self) -> Self {
-        let (res, _, _) = extended_gcd(Cow::Borrowed(self), Cow::Borrowed(other), false);
-        res.into_biguint().unwrap()
+        biguint_gcd(self, other)
     }
 
     /// Calculates the Lowest Common Multiple (LCM) of the number and `other`.
@@ -1924,6 +1978,10 @@ fn to_inexact_bitwise_digits_le(u: &BigUint, bits: usize) -> Vec<u8> {
 fn to_radix_digits_le(u: &BigUint, radix: u32) -> Vec<u8> {
     debug_assert!(!u.is_zero() && !radix.is_power_of_two());
 
+    if u.data.len() > radix_conversion_threshold() {
+        return to_radix_digits_le_large(u, radix);
+    }
+
     // Estimate how big the result will be, so we can pre-allocate it.
     let radix_digits = ((u.bits() as f64) / (radix as f64).log2()).ceil();
     let mut res = Vec::with_capacity(radix_digits as usize);
@@ -1950,6 +2008,60 @@ fn to_radix_digits_le(u: &BigUint, radix: u32) -> Vec<u8> {
     res
 }
 
+// Extract little-endian radix digits by dividing by radix^(power * 2^k) for the largest k that
+// makes the quotient and the remainder about the same size, and converting both recursively.
+fn to_radix_digits_le_large(u: &BigUint, radix: u32) -> Vec<u8> {
+    let radix_digits = ((u.bits() as f64) / (radix as f64).log2()).ceil() as usize;
+    // u has at most radix_digits + 1 digits (allowing for rounding), so u < powers[k]^2 for the
+    // last k:
+    let powers = radix_powers(radix, radix_digits / 2 + 1);
+
+    let mut res = Vec::with_capacity(radix_digits + 1);
+    to_radix_digits_le_rec(u.clone(), radix, &powers, powers.len() - 1, 0, &mut res);
+
+    // Only the high part is not zero padded, but it may have been zero.
+    while res.last() == Some(&0) {
+        res.pop();
+    }
+    res
+}
+
+// Appends the digits of u < powers[k]^2 to res, zero padded to `pad` digits.
+fn to_radix_digits_le_rec(
+    u: BigUint,
+    radix: u32,
+    powers: &[BigUint],
+    k: usize,
+    pad: usize,
+    res: &mut Vec<u8>,
+) {
+    if u.data.len() <= radix_conversion_threshold() {
+        let start = res.len();
+        if !u.is_zero() {
+            res.extend(to_radix_digits_le(&u, radix));
+        }
+        if res.len() < start + pad {
+            res.resize(start + pad, 0);
+        }
+        return;
+    }
+
+    // u has more than two digits, so k > 0 (as powers[0] is a single digit).
+    let (_, power) = get_radix_base(radix);
+    let low_digits = power << k;
+
+    let (high, low) = u.div_rem(&powers[k]);
+    to_radix_digits_le_rec(low, radix, powers, k - 1, low_digits, res);
+    to_radix_digits_le_rec(
+        high,
+        radix,
+        powers,
+        k - 1,
+        pad.saturating_sub(low_digits),
+        res,
+    );
+}
+
 pub fn to_radix_le(u: &BigUint, radix: u32) -> Vec<u8> {
     if u.is_zero() {
         vec![0]
@@ -2358,34 +2470,167 @@ impl BigUint {
 
         // For an odd modulus, we can use Montgomery multiplication in base 2^32.
         if modulus.is_odd() {
-            return monty_modpow(self, exponent, modulus);
+            return MontgomeryContext::new(modulus).modpow(self, exponent);
         }
 
-        // Otherwise do basically the same as `num::pow`, but with a modulus.
-        let one = BigUint::one();
-        if exponent.is_zero() {
-            return one;
+        // Otherwise split off the power of two and combine the results.
+        even_modpow(self, exponent, modulus)
+    }
+
+    /// Returns the product of `(base ^ exponent) % modulus` over all pairs, e.g.
+    /// `g^a * h^b % p` for `pairs = &[(&g, &a), (&h, &b)]`. An empty product is 1.
+    ///
+    /// For an odd modulus the exponentiations share their squarings, see
+    /// `MontgomeryContext::multi_modpow`, so two bases cost much less than two
+    /// calls to `modpow`. For an even modulus the powers are computed separately.
+    ///
+    /// Panics if the modulus is zero.
+    pub fn multi_modpow(pairs: &[(&BigUint, &BigUint)], modulus: &BigUint) -> BigUint {
+        assert!(!modulus.is_zero(), "divide by zero!");
+
+        if modulus.is_odd() {
+            return MontgomeryContext::new(modulus).multi_modpow(pairs);
         }
 
-        let mut base = self % modulus;
-        let mut exp = exponent.clone();
-        while exp.is_even() {
-            base = &base * &base % modulus;
-            exp >>= 1;
+        pairs.iter().fold(BigUint::one() % modulus, |acc, &(x, y)| {
+            acc * x.modpow(y, modulus) % modulus
+        })
+    }
+
+    /// Returns `(self ^ exponent) % modulus`, using an exponentiation whose
+    /// running time only depends on the lengths of the operands, for use with
+    /// secret exponents. See `MontgomeryContext::modpow_ct` for details.
+    ///
+    /// Panics if the modulus is even (which includes zero).
+    pub fn modpow_ct(&self, exponent: &Self, modulus: &Self) -> Self {
+        MontgomeryContext::new(modulus).modpow_ct(self, exponent)
+    }
+
+    /// Returns a square root of `self` modulo the prime `p`, or `None` if there is none.
+    /// Of the two roots `r` and `p - r`, the smaller one is returned.
+    ///
+    /// See `algorithms::sqrt_mod_prime` for details; `p` is not checked for primality.
+    ///
+    /// Panics if `p` is even and not 2 (which includes zero).
+    pub fn sqrt_mod_prime(&self, p: &Self) -> Option<Self> {
+        sqrt_mod_prime(self, p)
+    }
+
+    /// Returns all square roots of `self` modulo `p^k`, for a prime `p`, in increasing
+    /// order. See `algorithms::sqrt_mod_prime_power` for details.
+    ///
+    /// Panics if `p` is neither an odd number greater than one nor 2.
+    pub fn sqrt_mod_prime_power(&self, p: &Self, k: u32) -> Vec<Self> {
+        sqrt_mod_prime_power(self, p, k)
+    }
+
+    /// Returns all square roots of `self` modulo the product of the prime powers
+    /// `p^k` in `factors`, in increasing order. See `algorithms::sqrt_mod_factored`
+    /// for details.
+    ///
+    /// Panics if a prime appears twice or is neither odd nor 2.
+    pub fn sqrt_mod_factored(&self, factors: &[(BigUint, u32)]) -> Vec<Self> {
+        sqrt_mod_factored(self, factors)
+    }
+
+    /// Adds `b * c` to `self`, in place.
+    ///
+    /// Unlike `*self += &b * &c`, the product is accumulated directly into the digits of
+    /// `self`, without a temporary (apart from those of the multiplication algorithms for large
+    /// operands).
+    pub fn add_mul(&mut self, b: &BigUint, c: &BigUint) {
+        mac_unnormalized(self, b, c);
+        self.normalize();
+    }
+
+    /// Subtracts `b * c` from `self`, in place.
+    ///
+    /// Panics if `b * c` is larger than `self`, like the `-` operator.
+    pub fn sub_mul(&mut self, b: &BigUint, c: &BigUint) {
+        if b.is_zero() || c.is_zero() {
+            return;
         }
-        if exp == one {
-            return base;
+
+        let len = cmp::max(self.data.len(), b.data.len() + c.data.len());
+        self.data.resize(len, 0);
+        let wrapped = msc3(&mut self.data[..], &b.data[..], &c.data[..]);
+        assert!(
+            !wrapped,
+            "Cannot subtract b * c from a because b * c is larger than a."
+        );
+        self.normalize();
+    }
+
+    /// Returns the sum of the products of all pairs, `b0 * c0 + b1 * c1 + ...`.
+    ///
+    /// All products are accumulated into a single buffer, which is only normalized once at the
+    /// end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use num_bigint_dig::BigUint;
+    ///
+    /// let b = [BigUint::from(2u32), BigUint::from(3u32)];
+    /// let c = [BigUint::from(5u32), BigUint::from(7u32)];
+    /// assert_eq!(BigUint::dot_product(b.iter().zip(&c)), BigUint::from(31u32));
+    /// ```
+    pub fn dot_product<'a, I>(pairs: I) -> BigUint
+    where
+        I: IntoIterator<Item = (&'a BigUint, &'a BigUint)>,
+    {
+        let mut acc = BigUint::zero();
+        for (b, c) in pairs {
+            mac_unnormalized(&mut acc, b, c);
         }
+        acc.normalized()
+    }
 
-        let mut acc = base.clone();
-        while exp > one {
-            exp >>= 1;
-            base = &base * &base % modulus;
-            if exp.is_odd() {
-                acc = acc * &base % modulus;
-            }
+    /// Sets `out` to `a * b`, keeping all temporary values in `scratch`.
+    ///
+    /// Once `out` and `scratch` have grown large enough, this doesn't allocate, so it can be
+    /// used in loops that must not hit the allocator. To that end it only uses long and
+    /// Karatsuba multiplication; products whose smaller factor reaches `Thresholds::mul_ntt`
+    /// digits use the regular (allocating) multiplication instead.
+    pub fn mul_into(out: &mut BigUint, a: &BigUint, b: &BigUint, scratch: &mut Scratch) {
+        out.data.clear();
+        if a.is_zero() || b.is_zero() {
+            return;
+        }
+
+        let (x, y) = if a.data.len() < b.data.len() {
+            (&a.data, &b.data)
+        } else {
+            (&b.data, &a.data)
+        };
+        out.data.resize(x.len() + y.len() + 1, 0);
+
+        if x.len() >= mul_ntt_threshold() {
+            mac3(&mut out.data[..], x, y);
+        } else {
+            let karatsuba = mul_karatsuba_threshold();
+            let scratch = scratch.digits(mac3_scratch_len(y.len(), karatsuba));
+            mac3_scratch(&mut out.data[..], x, y, scratch, karatsuba);
         }
-        acc
+        out.normalize();
+    }
+
+    /// Sets `q` and `r` to the quotient and remainder of `a / b`, keeping all temporary values
+    /// in `scratch`.
+    ///
+    /// Once `q`, `r` and `scratch` have grown large enough, this doesn't allocate, unless both
+    /// the divisor and the quotient have at least `Thresholds::div_burnikel_ziegler` digits:
+    /// such divisions use the regular (allocating) recursive division instead.
+    ///
+    /// Panics if `b` is zero.
+    pub fn div_rem_into(
+        q: &mut BigUint,
+        r: &mut BigUint,
+        a: &BigUint,
+        b: &BigUint,
+        scratch: &mut Scratch,
+    ) {
+        div_rem_scratch(q, r, a, b, &mut scratch.digits);
     }
 
     /// Returns the truncated principal square root of `self` --
@@ -2421,6 +2666,153 @@ impl BigUint {
     }
 }
 
+/// Adds `b * c` to `acc`, growing it as needed but not normalizing it, so that many products
+/// can be accumulated without shrinking and growing the buffer in between.
+pub(crate) fn mac_unnormalized(acc: &mut BigUint, b: &BigUint, c: &BigUint) {
+    if b.is_zero() || c.is_zero() {
+        return;
+    }
+
+    // The top digit must be zero, so that the sum can't overflow.
+    let len = b.data.len() + c.data.len() + 1;
+    if acc.data.len() < len {
+        acc.data.resize(len, 0);
+    } else if acc.data.last() != Some(&0) {
+        acc.data.push(0);
+    }
+
+    // Only the same number counts as a square, comparing the digits would cost O(n) every time.
+    if ::std::ptr::eq(b, c) {
+        mac_sqr(&mut acc.data[..], &b.data[..]);
+    } else {
+        mac3(&mut acc.data[..], &b.data[..], &c.data[..]);
+    }
+}
+
+/// Calculates x ** y mod m for an even, nonzero m.
+///
+/// The modulus is split as m = 2^k * m_odd. The power is computed modulo
+/// m_odd using Montgomery multiplication and modulo 2^k by just truncating
+/// the intermediate products, then the two are recombined using the Chinese
+/// Remainder Theorem.
+///
+/// Squares and cubes use a `BarrettReducer` for m instead, which needs a
+/// single division to set up, where the split needs a Montgomery context and
+/// an inverse modulo 2^k. From three exponent bits on Barrett is no longer
+/// faster, as its reductions cost more than Montgomery's.
+fn even_modpow(x: &BigUint, y: &BigUint, m: &BigUint) -> BigUint {
+    debug_assert!(m.is_even() && !m.is_zero());
+
+    if y.bits() <= 2 {
+        return BarrettReducer::new(m).modpow(x, y);
+    }
+
+    let k = m.trailing_zeros().unwrap();
+    let m_odd = m >> k;
+
+    let r2 = pow2_modpow(x, y, k);
+    if m_odd.is_one() {
+        return r2;
+    }
+    let r1 = MontgomeryContext::new(&m_odd).modpow(x, y);
+
+    // We want r = r1 + m_odd * h with r = r2 mod 2^k, which gives
+    // h = (r2 - r1) * m_odd^-1 mod 2^k.
+    let mut r1_low = r1.clone();
+    truncate_bits(&mut r1_low, k);
+    let mut diff = (BigUint::one() << k) + r2 - r1_low;
+    truncate_bits(&mut diff, k);
+
+    let mut h = diff * inv_mod_pow2(&m_odd, k);
+    truncate_bits(&mut h, k);
+
+    r1 + m_odd * h
+}
+
+/// Calculates x ** y mod 2^k.
+fn pow2_modpow(x: &BigUint, y: &BigUint, k: usize) -> BigUint {
+    let mut base = x.clone();
+    truncate_bits(&mut base, k);
+
+    if y.is_zero() {
+        let mut one = BigUint::one();
+        truncate_bits(&mut one, k);
+        return one;
+    }
+
+    let mut exp = Cow::Borrowed(y);
+    if base.is_even() {
+        // x^y has at least y factors of two.
+        if y.bits() > 64 || y.to_u64().unwrap() >= k as u64 {
+            return BigUint::zero();
+        }
+    } else {
+        // The group of units modulo 2^k has order 2^(k-1) and exponent
+        // 2^max(k-2, 1), so the exponent can be reduced.
+        let e_bits = cmp::max(k, 3) - 2;
+        if y.bits() > e_bits {
+            let mut e = y.clone();
+            truncate_bits(&mut e, e_bits);
+            exp = Cow::Owned(e);
+        }
+    }
+
+    // Plain left-to-right square and multiply, dropping the high bits of every
+    // product.
+    let mut acc = BigUint::one();
+    for i in (0..exp.bits()).rev() {
+        acc = &acc * &acc;
+        truncate_bits(&mut acc, k);
+        if (exp.data[i / big_digit::BITS] >> (i % big_digit::BITS)) & 1 == 1 {
+            acc *= &base;
+            truncate_bits(&mut acc, k);
+        }
+    }
+    acc
+}
+
+/// Calculates a^-1 mod 2^k for an odd a, using Newton's iteration
+/// x' = x * (2 - a * x), which doubles the number of correct bits each step.
+fn inv_mod_pow2(a: &BigUint, k: usize) -> BigUint {
+    debug_assert!(a.is_odd());
+
+    // a * a = 1 mod 8 for every odd a.
+    let mut x = a.clone();
+    truncate_bits(&mut x, 3);
+    let mut bits = 3;
+
+    while bits < k {
+        bits = cmp::min(2 * bits, k);
+
+        let mut ax = a * &x;
+        truncate_bits(&mut ax, bits);
+        // 2 - a * x, as a positive number mod 2^bits
+        let mut t = (BigUint::one() << bits) + 2u32 - ax;
+        truncate_bits(&mut t, bits);
+
+        x *= t;
+        truncate_bits(&mut x, bits);
+    }
+
+    truncate_bits(&mut x, k);
+    x
+}
+
+/// Reduces x modulo 2^k, in place.
+fn truncate_bits(x: &mut BigUint, k: usize) {
+    let words = Integer::div_ceil(&k, &big_digit::BITS);
+    if x.data.len() >= words {
+        x.data.truncate(words);
+        let rem = k % big_digit::BITS;
+        if rem != 0 {
+            if let Some(last) = x.data.last_mut() {
+                *last &= (1 << rem) - 1;
+            }
+        }
+    }
+    x.normalize();
+}
+
 /// Returns the number of least-significant bits that are zero,
 /// or `None` if the entire number is zero.
 pub fn trailing_zeros(u: &BigUint) -> Option<usize> {
@@ -0,0 +1,201 @@
+use integer::Integer;
+use num_traits::{One, Zero};
+
+use biguint::{BigUint, IntoBigUint};
+use traits::ModInverse;
+
+/// Solves the system of congruences `x = r_i mod m_i` given as pairs `(r_i, m_i)`.
+///
+/// Returns the smallest solution `x` and the least common multiple of the moduli,
+/// which together describe all solutions, or `None` if the congruences contradict
+/// each other. The moduli don't need to be coprime. The residues may be larger than
+/// their moduli. An empty system is solved by `(0, 1)`.
+///
+/// This builds a `CrtContext` for a single reconstruction; use one directly to solve
+/// several systems with the same moduli.
+///
+/// Panics if a modulus is zero.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint_dig::{crt, BigUint};
+///
+/// let pair = |r: u32, m: u32| (BigUint::from(r), BigUint::from(m));
+///
+/// let (x, m) = crt(&[pair(2, 3), pair(3, 5), pair(2, 7)]).unwrap();
+/// assert_eq!((x, m), (BigUint::from(23u32), BigUint::from(105u32)));
+///
+/// let (x, m) = crt(&[pair(3, 4), pair(5, 6)]).unwrap();
+/// assert_eq!((x, m), (BigUint::from(11u32), BigUint::from(12u32)));
+///
+/// assert_eq!(crt(&[pair(1, 4), pair(2, 6)]), None);
+/// ```
+pub fn crt(congruences: &[(BigUint, BigUint)]) -> Option<(BigUint, BigUint)> {
+    let moduli: Vec<BigUint> = congruences.iter().map(|(_, m)| m.clone()).collect();
+    let residues: Vec<BigUint> = congruences.iter().map(|(r, _)| r.clone()).collect();
+
+    let ctx = CrtContext::new(&moduli);
+    let x = ctx.reconstruct(&residues)?;
+    Some((x, ctx.modulus))
+}
+
+/// A Chinese remainder theorem context for a fixed list of nonzero moduli.
+///
+/// The congruences are combined pairwise along a balanced binary tree, so that the
+/// numbers in most merges are small and the last merges profit from fast
+/// multiplication. Building the context computes the gcd of each pair of merged
+/// moduli and the modular inverses the merges need, so every `reconstruct` only
+/// takes a few multiplications and divisions per modulus.
+///
+/// The moduli don't need to be coprime: merging `x = r_1 mod m_1` and
+/// `x = r_2 mod m_2` with `g = gcd(m_1, m_2)` requires `r_1 = r_2 mod g`, and gives a
+/// congruence modulo `lcm(m_1, m_2)`.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint_dig::{BigUint, CrtContext};
+///
+/// let moduli = [BigUint::from(1000003u32), BigUint::from(1000033u32)];
+/// let ctx = CrtContext::new(&moduli);
+///
+/// let x = BigUint::from(123_456_789_012u64);
+/// let residues: Vec<BigUint> = moduli.iter().map(|m| &x % m).collect();
+/// assert_eq!(ctx.reconstruct(&residues), Some(x));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CrtContext {
+    /// The least common multiple of the moduli.
+    modulus: BigUint,
+    moduli: Vec<BigUint>,
+    /// The merges of each level of the tree, from the leaves up. A level with an odd
+    /// number of congruences passes the last one on unchanged.
+    levels: Vec<Vec<Merge>>,
+}
+
+/// Merges `x = r_1 mod m_1` with `x = r_2 mod g * m_2` into `x = r_1 + m_1 * k`.
+#[derive(Clone, Debug)]
+struct Merge {
+    m1: BigUint,
+    /// `gcd(m_1, m_2)` of the original moduli.
+    g: BigUint,
+    /// The second modulus divided by `g`, which is coprime to `m_1 / g`.
+    m2: BigUint,
+    /// `(m_1 / g)^-1 mod m2`.
+    inv: BigUint,
+}
+
+impl CrtContext {
+    /// Creates a new context for the given moduli.
+    ///
+    /// Panics if a modulus is zero.
+    pub fn new(moduli: &[BigUint]) -> Self {
+        assert!(moduli.iter().all(|m| !m.is_zero()), "divide by zero!");
+
+        let mut levels = Vec::new();
+        let mut current = moduli.to_vec();
+        while current.len() > 1 {
+            let mut level = Vec::with_capacity(current.len() / 2);
+            let mut next = Vec::with_capacity(Integer::div_ceil(&current.len(), &2));
+            for pair in current.chunks(2) {
+                if pair.len() == 1 {
+                    next.push(pair[0].clone());
+                    continue;
+                }
+
+                let g = pair[0].gcd(&pair[1]);
+                let m2 = &pair[1] / &g;
+                let inv = (&pair[0] / &g % &m2)
+                    .mod_inverse(&m2)
+                    .unwrap()
+                    .into_biguint()
+                    .unwrap();
+
+                next.push(&pair[0] * &m2);
+                level.push(Merge {
+                    m1: pair[0].clone(),
+                    g,
+                    m2,
+                    inv,
+                });
+            }
+            levels.push(level);
+            current = next;
+        }
+
+        CrtContext {
+            modulus: current.pop().unwrap_or_else(BigUint::one),
+            moduli: moduli.to_vec(),
+            levels,
+        }
+    }
+
+    /// Returns the least common multiple of the moduli, the modulus of the
+    /// reconstructed values.
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    /// Returns the moduli of this context.
+    pub fn moduli(&self) -> &[BigUint] {
+        &self.moduli
+    }
+
+    /// Returns the smallest `x` with `x = residues[i] mod moduli[i]` for all `i`, or
+    /// `None` if there is no such `x`. The residues may be larger than their moduli.
+    ///
+    /// Panics if the number of residues differs from the number of moduli.
+    pub fn reconstruct(&self, residues: &[BigUint]) -> Option<BigUint> {
+        assert_eq!(
+            residues.len(),
+            self.moduli.len(),
+            "one residue per modulus is needed"
+        );
+
+        let mut current: Vec<BigUint> = residues
+            .iter()
+            .zip(&self.moduli)
+            .map(|(r, m)| r % m)
+            .collect();
+
+        for level in &self.levels {
+            let mut next = Vec::with_capacity(Integer::div_ceil(&current.len(), &2));
+            for (i, pair) in current.chunks(2).enumerate() {
+                if pair.len() == 1 {
+                    next.push(pair[0].clone());
+                } else {
+                    next.push(level[i].merge(&pair[0], &pair[1])?);
+                }
+            }
+            current = next;
+        }
+
+        Some(current.pop().unwrap_or_else(BigUint::zero))
+    }
+}
+
+impl Merge {
+    /// Returns the `x < m_1 * m_2` with `x = r1 mod m_1` and `x = r2 mod g * m_2`, for
+    /// `r1 < m_1` and `r2 < g * m_2`.
+    fn merge(&self, r1: &BigUint, r2: &BigUint) -> Option<BigUint> {
+        // r1 + m_1 * k = r2 mod g * m_2 if and only if r1 = r2 mod g and
+        // k = (r2 - r1) / g * (m_1 / g)^-1 mod m_2.
+        let (diff, neg) = if r2 >= r1 {
+            (r2 - r1, false)
+        } else {
+            (r1 - r2, true)
+        };
+        let (d, rem) = diff.div_rem(&self.g);
+        if !rem.is_zero() {
+            return None;
+        }
+
+        let mut d = d % &self.m2;
+        if neg && !d.is_zero() {
+            d = &self.m2 - d;
+        }
+        let k = d * &self.inv % &self.m2;
+        Some(r1 + &self.m1 * k)
+    }
+}
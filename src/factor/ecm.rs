@@ -0,0 +1,290 @@
+use integer::Integer;
+use num_traits::{One, Zero};
+use rand::Rng;
+
+use super::trial::PrimeIter;
+use crate::bigrand::RandBigInt;
+use crate::traits::ModInverse;
+use crate::{BigUint, IntoBigUint, MontgomeryContext};
+
+/// Bounds and number of curves for `ecm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EcmParams {
+    /// Stage 1 bound: a curve finds a prime `p` if its group order modulo `p`
+    /// is a product of prime powers up to `b1`, apart from one prime up to `b2`.
+    pub b1: u64,
+    /// Stage 2 bound. Stage 2 is skipped if `b2 <= b1`.
+    pub b2: u64,
+    /// Number of curves to try.
+    pub curves: usize,
+}
+
+/// Factor digits, stage 1 bound and number of curves, following the usual
+/// GMP-ECM choices of `b1`, with more curves for the smaller `b2 = 100 * b1`.
+const LEVELS: [(usize, u64, usize); 8] = [
+    (15, 2_000, 25),
+    (20, 11_000, 90),
+    (25, 50_000, 300),
+    (30, 250_000, 700),
+    (35, 1_000_000, 1_800),
+    (40, 3_000_000, 5_100),
+    (45, 11_000_000, 10_600),
+    (50, 43_000_000, 19_300),
+];
+
+impl EcmParams {
+    /// Returns parameters for finding a prime factor of up to `digits`
+    /// decimal digits. The number of curves is about the expected number
+    /// needed for a factor of that size, so such a factor is found with a
+    /// probability of roughly 1 − 1/e; more curves raise the odds.
+    ///
+    /// Sizes above 50 digits get the parameters for 50 digits.
+    pub fn for_digits(digits: usize) -> Self {
+        let &(_, b1, curves) = LEVELS
+            .iter()
+            .find(|&&(d, _, _)| d >= digits)
+            .unwrap_or(&LEVELS[LEVELS.len() - 1]);
+        EcmParams {
+            b1,
+            b2: 100 * b1,
+            curves,
+        }
+    }
+}
+
+impl Default for EcmParams {
+    /// The parameters for factors of up to 20 digits.
+    fn default() -> Self {
+        EcmParams::for_digits(20)
+    }
+}
+
+/// Looks for a nontrivial factor of `n` with Lenstra's elliptic curve method.
+///
+/// Each curve is a Montgomery curve `By^2 = x^3 + Ax^2 + x` from Suyama's
+/// parametrization, whose group order is divisible by 12, with the random
+/// parameter drawn from `rng`, so the result only depends on the state of
+/// `rng`. Points are kept in projective `(X : Z)` coordinates, which needs
+/// no inversions. Stage 1 multiplies the starting point by all prime powers
+/// up to `b1` with the Montgomery ladder; stage 2 covers each prime
+/// `b1 < q <= b2` by one comparison of a giant step `[mD]P` and a baby step
+/// `[j]P`, with `q = mD ± j`.
+///
+/// Returns `None` if `n` is less than 4 or none of the curves found a factor.
+pub fn ecm<R: Rng + ?Sized>(n: &BigUint, params: &EcmParams, rng: &mut R) -> Option<BigUint> {
+    if n < &BigUint::from(4u32) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigUint::from(2u32));
+    }
+    // The odd numbers below 8 are prime.
+    if n < &BigUint::from(8u32) {
+        return None;
+    }
+
+    let ctx = MontgomeryContext::new(n);
+    let low = BigUint::from(6u32);
+    let high = n - 1u32;
+    for _ in 0..params.curves {
+        let sigma = rng.gen_biguint_range(&low, &high);
+        if let Some(g) = ecm_curve(&ctx, &sigma, params) {
+            return Some(g);
+        }
+    }
+    None
+}
+
+/// A point in `(X : Z)` coordinates, in Montgomery form.
+#[derive(Clone)]
+struct Point {
+    x: BigUint,
+    z: BigUint,
+}
+
+struct Curve<'a> {
+    ctx: &'a MontgomeryContext,
+    n: &'a BigUint,
+    /// `(A + 2) / 4`, in Montgomery form.
+    a24: BigUint,
+}
+
+impl<'a> Curve<'a> {
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.ctx.mul(a, b)
+    }
+
+    fn add_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let c = a + b;
+        if &c >= self.n {
+            c - self.n
+        } else {
+            c
+        }
+    }
+
+    fn sub_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if a >= b {
+            a - b
+        } else {
+            a + self.n - b
+        }
+    }
+
+    /// Returns `2P`.
+    fn double(&self, p: &Point) -> Point {
+        let s = self.add_mod(&p.x, &p.z);
+        let d = self.sub_mod(&p.x, &p.z);
+        let ss = self.mul(&s, &s);
+        let dd = self.mul(&d, &d);
+        let t = self.sub_mod(&ss, &dd);
+        let x = self.mul(&ss, &dd);
+        let z = self.mul(&t, &self.add_mod(&dd, &self.mul(&self.a24, &t)));
+        Point { x, z }
+    }
+
+    /// Returns `P + Q`, given `P - Q`.
+    fn add(&self, p: &Point, q: &Point, diff: &Point) -> Point {
+        let u = self.mul(&self.sub_mod(&p.x, &p.z), &self.add_mod(&q.x, &q.z));
+        let v = self.mul(&self.add_mod(&p.x, &p.z), &self.sub_mod(&q.x, &q.z));
+        let s = self.add_mod(&u, &v);
+        let d = self.sub_mod(&u, &v);
+        Point {
+            x: self.mul(&diff.z, &self.mul(&s, &s)),
+            z: self.mul(&diff.x, &self.mul(&d, &d)),
+        }
+    }
+
+    /// Returns `[k]P` for `k >= 1`, with the Montgomery ladder.
+    fn scale(&self, p: &Point, k: u64) -> Point {
+        // Invariant: r1 - r0 = P.
+        let mut r0 = p.clone();
+        let mut r1 = self.double(p);
+        for i in (0..63 - k.leading_zeros()).rev() {
+            if (k >> i) & 1 == 1 {
+                r0 = self.add(&r1, &r0, p);
+                r1 = self.double(&r1);
+            } else {
+                r1 = self.add(&r1, &r0, p);
+                r0 = self.double(&r0);
+            }
+        }
+        r0
+    }
+}
+
+/// Number of stage 2 primes per gcd.
+const STAGE2_CHUNK: usize = 4096;
+
+/// Stage 2 giant step size, divisible by 2, 3, 5, 7 and 11.
+const D: u64 = 2310;
+
+/// Returns `g` if it is a nontrivial factor of `n`.
+fn nontrivial(g: BigUint, n: &BigUint) -> Option<BigUint> {
+    if g.is_one() || &g == n {
+        None
+    } else {
+        Some(g)
+    }
+}
+
+/// Runs stage 1 and 2 on the curve for `sigma`.
+fn ecm_curve(ctx: &MontgomeryContext, sigma: &BigUint, params: &EcmParams) -> Option<BigUint> {
+    let n = ctx.modulus();
+
+    // Suyama: u = sigma^2 - 5, v = 4 sigma, P = (u^3 : v^3) and
+    // (A + 2) / 4 = (v - u)^3 (3u + v) / (16 u^3 v).
+    let u = (sigma * sigma - 5u32) % n;
+    let v = (sigma << 2) % n;
+    let u3 = u.modpow(&BigUint::from(3u32), n);
+    let v3 = v.modpow(&BigUint::from(3u32), n);
+    let vu = (&v + n - &u) % n;
+    let num = vu.modpow(&BigUint::from(3u32), n) * ((&u * 3u32 + &v) % n) % n;
+    let den = ((&u3 * &v) << 4) % n;
+    let inv = match (&den).mod_inverse(n) {
+        Some(inv) => inv.into_biguint().unwrap(),
+        None => return nontrivial(den.gcd(n), n),
+    };
+
+    let curve = Curve {
+        ctx,
+        n,
+        a24: ctx.to_montgomery(&(num * inv % n)),
+    };
+    let mut p = Point {
+        x: ctx.to_montgomery(&u3),
+        z: ctx.to_montgomery(&v3),
+    };
+
+    // Stage 1: multiply by the largest power of each prime up to b1.
+    for q in PrimeIter::new(2, params.b1) {
+        let mut power = q;
+        while power <= params.b1 / q {
+            power *= q;
+        }
+        p = curve.scale(&p, power);
+    }
+
+    let g = p.z.gcd(n);
+    if !g.is_one() {
+        return nontrivial(g, n);
+    }
+    if params.b2 <= params.b1 {
+        return None;
+    }
+
+    // Stage 2: [q]P = 0 for q = mD ± j if and only if [mD]P = ±[j]P, that is
+    // if their x coordinates match, so X_m Z_j - X_j Z_m shares the factor.
+    // baby[i] = [2i + 1]P covers all odd j <= D / 2.
+    let p2 = curve.double(&p);
+    let mut baby = vec![p.clone(), curve.add(&p2, &p, &p)];
+    while baby.len() <= (D / 4) as usize {
+        let i = baby.len();
+        let next = curve.add(&baby[i - 1], &p2, &baby[i - 2]);
+        baby.push(next);
+    }
+
+    let step = curve.scale(&p, D);
+    let mut m = 0;
+    let mut giant = Point {
+        x: BigUint::zero(),
+        z: BigUint::zero(),
+    };
+    let mut next_giant = giant.clone();
+
+    let mut acc = ctx.to_montgomery(&BigUint::one());
+    let mut count = 0;
+    for q in PrimeIter::new(params.b1 + 1, params.b2) {
+        let qm = (q + D / 2) / D;
+        if qm == 0 {
+            // Only for q < D / 2, which can just be multiplied directly.
+            acc = curve.mul(&acc, &curve.scale(&p, q).z);
+        } else {
+            if m == 0 {
+                m = qm;
+                giant = curve.scale(&p, m * D);
+                next_giant = curve.scale(&p, (m + 1) * D);
+            }
+            while m < qm {
+                let next = curve.add(&next_giant, &step, &giant);
+                giant = ::std::mem::replace(&mut next_giant, next);
+                m += 1;
+            }
+
+            let j = if q > m * D { q - m * D } else { m * D - q };
+            let b = &baby[(j / 2) as usize];
+            let diff = curve.sub_mod(&curve.mul(&giant.x, &b.z), &curve.mul(&b.x, &giant.z));
+            acc = curve.mul(&acc, &diff);
+        }
+
+        count += 1;
+        if count % STAGE2_CHUNK == 0 {
+            let g = acc.gcd(n);
+            if !g.is_one() {
+                return nontrivial(g, n);
+            }
+        }
+    }
+
+    nontrivial(acc.gcd(n), n)
+}
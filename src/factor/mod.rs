@@ -0,0 +1,190 @@
+//! Integer factorization.
+//!
+//! `factor` splits a number into its prime factors. Factors below 2^16 are
+//! removed by trial division, and the remaining composites are split with
+//! Pollard's p − 1 and rho methods, the elliptic curve method and the
+//! quadratic sieve, until `probably_prime` accepts every cofactor. The
+//! building blocks are available on their own as well.
+//!
+//! The rho method needs about `sqrt(p)` steps to find a prime factor `p`, so
+//! it is only used for small factors. ECM finds factors of 30 digits in
+//! minutes, and the time grows subexponentially with the size of the factor.
+//! The running time of the quadratic sieve only depends on the size of the
+//! number, from milliseconds for 30 digits to seconds for 60 digits.
+
+use num_traits::{One, Pow};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fmt;
+
+use crate::prime::probably_prime;
+use crate::BigUint;
+
+mod ecm;
+mod pm1;
+mod rho;
+mod siqs;
+mod trial;
+
+pub use self::ecm::{ecm, EcmParams};
+pub use self::pm1::pollard_pm1;
+pub use self::rho::pollard_rho_brent;
+pub use self::siqs::siqs;
+pub use self::trial::{primes_up_to, trial_division};
+
+use self::trial::{SMALL_PRIMES, SMALL_PRIME_BOUND};
+
+/// Number of Miller-Rabin rounds `factor` uses to accept a prime factor.
+const PRIME_REPS: usize = 20;
+
+/// Stage bounds of the p − 1 attempt in `factor`.
+const PM1_B1: u64 = 10_000;
+const PM1_B2: u64 = 500_000;
+
+/// Iterations of the rho method in `factor`, enough for factors up to about 2^32.
+const RHO_ITERATIONS: u64 = 1 << 17;
+
+/// Size in bits from which `factor` uses the quadratic sieve, instead of
+/// only ECM.
+const SIQS_BITS: usize = 64;
+
+/// The factorization of a positive integer into primes, as `(prime, exponent)`
+/// pairs in increasing order of the primes. One has no factors.
+///
+/// The pairs can be passed to `algorithms::sqrt_mod_factored` directly.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Factorization {
+    factors: Vec<(BigUint, u32)>,
+}
+
+impl Factorization {
+    /// Creates the empty factorization of one.
+    pub fn new() -> Self {
+        Factorization::default()
+    }
+
+    /// Returns the `(prime, exponent)` pairs, in increasing order of the primes.
+    pub fn factors(&self) -> &[(BigUint, u32)] {
+        &self.factors
+    }
+
+    /// Returns the `(prime, exponent)` pairs, in increasing order of the primes.
+    pub fn into_factors(self) -> Vec<(BigUint, u32)> {
+        self.factors
+    }
+
+    /// Returns the number that this is the factorization of.
+    pub fn value(&self) -> BigUint {
+        self.factors.iter().map(|&(ref p, e)| p.pow(e)).product()
+    }
+
+    /// Multiplies the factorization by `p^e`, for a prime `p`.
+    pub(crate) fn insert(&mut self, p: BigUint, e: u32) {
+        match self.factors.binary_search_by(|(q, _)| q.cmp(&p)) {
+            Ok(i) => self.factors[i].1 += e,
+            Err(i) => self.factors.insert(i, (p, e)),
+        }
+    }
+}
+
+impl fmt::Display for Factorization {
+    /// Formats the factorization as, e.g., `2^3 * 3 * 5`, or `1` if it is empty.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.factors.is_empty() {
+            return write!(f, "1");
+        }
+        for (i, &(ref p, e)) in self.factors.iter().enumerate() {
+            if i > 0 {
+                write!(f, " * ")?;
+            }
+            write!(f, "{}", p)?;
+            if e > 1 {
+                write!(f, "^{}", e)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the factorization of `n` into primes.
+///
+/// The prime factors are only probable primes, accepted by `probably_prime`
+/// with 20 rounds. Composites of at least 64 bits are split by the quadratic
+/// sieve, after a short search for much smaller factors with ECM, and
+/// smaller ones by ECM with growing bounds. The random choices use a fixed
+/// seed, so the result and the running time are reproducible. There is no
+/// bound on the running time, which is in the order of minutes for 70 digits.
+///
+/// Panics if `n` is zero.
+pub fn factor(n: &BigUint) -> Factorization {
+    let (mut factors, rest) = trial_division(n, SMALL_PRIME_BOUND - 1);
+
+    // Numbers waiting to be split, with their multiplicity.
+    let mut todo = vec![(rest, 1)];
+    while let Some((m, e)) = todo.pop() {
+        if m.is_one() {
+            continue;
+        }
+
+        // All prime factors are at least 2^16, so below 2^32 m must be prime.
+        if m.bits() <= 32 || probably_prime(&m, PRIME_REPS) {
+            factors.insert(m, e);
+            continue;
+        }
+
+        if let Some((root, k)) = perfect_power(&m) {
+            todo.push((root, e * k));
+            continue;
+        }
+
+        let d = find_factor(&m);
+        todo.push((&m / &d, e));
+        todo.push((d, e));
+    }
+
+    factors
+}
+
+/// Returns `(r, k)` with `r^k = n` and prime `k`, if `n` is a perfect power.
+/// `n` must not have prime factors below 2^16.
+fn perfect_power(n: &BigUint) -> Option<(BigUint, u32)> {
+    let max_k = n.bits() / 16;
+    for &k in SMALL_PRIMES.iter().take_while(|&&k| k as usize <= max_k) {
+        let k = k as u32;
+        let root = n.nth_root(k);
+        if &root.pow(k) == n {
+            return Some((root, k));
+        }
+    }
+    None
+}
+
+/// Returns a nontrivial factor of the odd composite `n`.
+fn find_factor(n: &BigUint) -> BigUint {
+    if let Some(d) = pollard_pm1(n, PM1_B1, PM1_B2) {
+        return d;
+    }
+    if let Some(d) = pollard_rho_brent(n, 1, RHO_ITERATIONS) {
+        return d;
+    }
+
+    let mut rng = StdRng::from_seed([0; 32]);
+    if n.bits() >= SIQS_BITS {
+        // Factors of up to a quarter of the digits are cheaper to find by ECM.
+        let digits = n.bits() * 3 / 40;
+        for digits in (15..=digits).step_by(5) {
+            if let Some(d) = ecm(n, &EcmParams::for_digits(digits), &mut rng) {
+                return d;
+            }
+        }
+        if let Some(d) = siqs(n) {
+            return d;
+        }
+    }
+
+    (15..)
+        .step_by(5)
+        .filter_map(|digits| ecm(n, &EcmParams::for_digits(digits), &mut rng))
+        .next()
+        .unwrap()
+}
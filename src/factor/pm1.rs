@@ -0,0 +1,135 @@
+use integer::Integer;
+use num_traits::{One, Zero};
+use std::cmp;
+
+use super::trial::PrimeIter;
+use crate::{BigUint, MontgomeryContext};
+
+/// Number of stage 1 prime powers per exponentiation, and of stage 2 primes
+/// per gcd.
+const STAGE1_CHUNK: usize = 64;
+const STAGE2_CHUNK: usize = 1024;
+
+/// The outcome of `gcd(x, n)` for a candidate `x`.
+enum Split {
+    None,
+    Factor(BigUint),
+    All,
+}
+
+fn split(x: &BigUint, n: &BigUint) -> Split {
+    let g = x.gcd(n);
+    if g.is_one() {
+        Split::None
+    } else if &g == n {
+        Split::All
+    } else {
+        Split::Factor(g)
+    }
+}
+
+/// The largest power of `p` that is at most `bound`.
+fn prime_power(p: u64, bound: u64) -> BigUint {
+    let mut q = p;
+    while q <= bound / p {
+        q *= p;
+    }
+    BigUint::from(q)
+}
+
+/// Looks for a nontrivial factor of `n` with Pollard's p − 1 method.
+///
+/// This finds a prime factor `p` if `p - 1` is a product of prime powers up
+/// to `b1` and at most one further prime up to `b2`. Stage 1 computes
+/// `a = 3^E mod n`, where `E` is the product of all prime powers up to `b1`,
+/// and stage 2 multiplies `a^q - 1` for all primes `b1 < q <= b2`, walking
+/// from one prime to the next with a table of `a^d` for the prime gaps `d`.
+/// Pass `b2 <= b1` to skip stage 2.
+///
+/// Returns `None` if `n` is less than 4 or no factor was found, including
+/// the rare case where all prime factors of `n` are found at once in stage 2.
+pub fn pollard_pm1(n: &BigUint, b1: u64, b2: u64) -> Option<BigUint> {
+    if n < &BigUint::from(4u32) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigUint::from(2u32));
+    }
+
+    // The base must be a unit, or stage 1 ends at zero.
+    if (n % 3u32).is_zero() {
+        return Some(BigUint::from(3u32));
+    }
+
+    let ctx = MontgomeryContext::new(n);
+
+    // Not 2, which has a small order modulo Mersenne and Fermat numbers.
+    let mut a = BigUint::from(3u32);
+    let mut stage1 = PrimeIter::new(2, b1);
+    loop {
+        let chunk: Vec<u64> = stage1.by_ref().take(STAGE1_CHUNK).collect();
+        if chunk.is_empty() {
+            break;
+        }
+
+        let e: BigUint = chunk.iter().map(|&p| prime_power(p, b1)).product();
+        let next = ctx.modpow(&a, &e);
+        match split(&(&next - 1u32), n) {
+            Split::None => a = next,
+            Split::Factor(g) => return Some(g),
+            Split::All => {
+                // Too much at once, so go back and take one prime at a time.
+                for &p in &chunk {
+                    let next = ctx.modpow(&a, &prime_power(p, b1));
+                    match split(&(&next - 1u32), n) {
+                        Split::None => a = next,
+                        Split::Factor(g) => return Some(g),
+                        Split::All => return None,
+                    }
+                }
+                return None;
+            }
+        }
+    }
+
+    // Stage 2 only walks over odd primes, with even gaps.
+    let mut stage2 = PrimeIter::new(cmp::max(b1 + 1, 3), b2);
+    let mut q = stage2.next()?;
+
+    // In Montgomery form, like the rho method; the gcds are not affected.
+    let one = ctx.to_montgomery(&BigUint::one());
+    let mut x = ctx.to_montgomery(&ctx.modpow(&a, &BigUint::from(q)));
+    let a = ctx.to_montgomery(&a);
+    let a2 = ctx.mul(&a, &a);
+    let sub_one = |x: &BigUint| if x >= &one { x - &one } else { x + n - &one };
+
+    // gaps[i] = a^(2i), for the even gaps between consecutive primes.
+    let mut gaps = vec![one.clone()];
+    let mut acc = one.clone();
+    for i in 0.. {
+        acc = ctx.mul(&acc, &sub_one(&x));
+
+        let next = stage2.next();
+        if i % STAGE2_CHUNK == STAGE2_CHUNK - 1 || next.is_none() {
+            match split(&acc, n) {
+                Split::None => {}
+                Split::Factor(g) => return Some(g),
+                Split::All => return None,
+            }
+        }
+
+        let next = match next {
+            Some(next) => next,
+            None => break,
+        };
+        let gap = ((next - q) / 2) as usize;
+        while gaps.len() <= gap {
+            let power = ctx.mul(&gaps[gaps.len() - 1], &a2);
+            gaps.push(power);
+        }
+        x = ctx.mul(&x, &gaps[gap]);
+        q = next;
+    }
+
+    None
+}
@@ -0,0 +1,96 @@
+use integer::Integer;
+use num_traits::One;
+use std::cmp;
+
+use crate::{BigUint, MontgomeryContext};
+
+/// Number of products `|x - y|` that are multiplied together before taking a gcd.
+const BATCH: u64 = 128;
+
+/// Looks for a nontrivial factor of `n` with Pollard's rho method, iterating
+/// `x -> x^2 + c mod n` from `x = 2` with Brent's cycle detection.
+///
+/// The differences of the sequence are multiplied together in batches, so
+/// only one gcd is needed per 128 iterations; a batch whose gcd is `n` is
+/// replayed one step at a time. Finding a prime factor `p` takes about
+/// `sqrt(p)` iterations.
+///
+/// Returns `None` if `n` is less than 4, if the sequence cycles modulo all
+/// prime factors of `n` at once (then try another `c`), or if no factor was
+/// found within `max_iterations` iterations. For a prime `n` there is no factor to
+/// find, so the result is `None` as well.
+pub fn pollard_rho_brent(n: &BigUint, c: u64, max_iterations: u64) -> Option<BigUint> {
+    if n < &BigUint::from(4u32) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigUint::from(2u32));
+    }
+
+    // Everything stays in Montgomery form, where x -> x^2 + c is just as good a
+    // pseudo-random map, and the gcds are not affected by the factors R.
+    let ctx = MontgomeryContext::new(n);
+    let c = ctx.to_montgomery(&(BigUint::from(c) % n));
+    let f = |x: &BigUint| {
+        let y = ctx.mul(x, x) + &c;
+        if &y >= n {
+            y - n
+        } else {
+            y
+        }
+    };
+    let diff = |x: &BigUint, y: &BigUint| if x >= y { x - y } else { y - x };
+
+    let mut y = ctx.to_montgomery(&BigUint::from(2u32));
+    let mut x = y.clone();
+    let mut ys = y.clone();
+    let mut q = ctx.to_montgomery(&BigUint::one());
+    let mut g = BigUint::one();
+    let mut r = 1;
+    let mut iterations = 0;
+
+    // x is the sequence element at the last power of two r, y runs through the
+    // next r elements, so a cycle of any length is eventually detected.
+    while g.is_one() {
+        x = y.clone();
+        for _ in 0..r {
+            y = f(&y);
+        }
+        iterations += r;
+
+        let mut k = 0;
+        while k < r && g.is_one() {
+            ys = y.clone();
+            let steps = cmp::min(BATCH, r - k);
+            for _ in 0..steps {
+                y = f(&y);
+                q = ctx.mul(&q, &diff(&x, &y));
+            }
+            g = q.gcd(n);
+            k += steps;
+            iterations += steps;
+        }
+
+        if iterations >= max_iterations && g.is_one() {
+            return None;
+        }
+        r *= 2;
+    }
+
+    if &g == n {
+        // Replay the last batch until the first difference with a common factor.
+        loop {
+            ys = f(&ys);
+            g = diff(&x, &ys).gcd(n);
+            if !g.is_one() {
+                break;
+            }
+        }
+    }
+
+    if &g == n {
+        None
+    } else {
+        Some(g)
+    }
+}
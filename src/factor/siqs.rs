@@ -0,0 +1,638 @@
+use integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::LN_2;
+
+use super::trial::{lower_bound, PrimeIter};
+use crate::algorithms::jacobi;
+use crate::bigint::magnitude;
+use crate::{BigInt, BigUint};
+
+/// Factor base size and sieve interval half-width `M`, by the size of `kN` in
+/// bits. Sizes in between are interpolated.
+const PARAMS: [(u64, usize, usize); 10] = [
+    (40, 50, 1 << 12),
+    (64, 100, 1 << 14),
+    (128, 500, 1 << 15),
+    (160, 1_400, 1 << 16),
+    (183, 2_800, 1 << 16),
+    (200, 4_200, 3 << 15),
+    (216, 6_300, 1 << 17),
+    (233, 10_000, 3 << 16),
+    (266, 17_000, 1 << 18),
+    (332, 40_000, 1 << 18),
+];
+
+/// Multipliers tried by `choose_multiplier`, the odd squarefree numbers below 75.
+const MULTIPLIERS: [u32; 31] = [
+    1, 3, 5, 7, 11, 13, 15, 17, 19, 21, 23, 29, 31, 33, 35, 37, 39, 41, 43, 47, 51, 53, 55, 57, 59,
+    61, 65, 67, 69, 71, 73,
+];
+
+/// Cofactors up to this multiple of the largest factor base prime are kept
+/// as large primes.
+const LARGE_PRIME_MULTIPLIER: u64 = 128;
+
+/// Primes below this bound are not sieved, which the threshold allows for.
+const MIN_SIEVE_PRIME: u32 = 12;
+
+/// Slack in the sieve threshold, mostly for the primes that are not sieved.
+const SMALL_PRIME_BITS: f64 = 8.0;
+
+/// Preferred size of the primes in `a`.
+const A_PRIME_SIZE: u32 = 2_000;
+
+/// Relations beyond the number of columns that are collected before each
+/// attempt to combine them into a square.
+const EXTRA_RELATIONS: usize = 32;
+
+/// Attempts of the square root step before giving up, each with more relations.
+const MAX_ROUNDS: usize = 4;
+
+/// Looks for a nontrivial factor of `n` with the self-initializing
+/// quadratic sieve.
+///
+/// A multiplier `k` is chosen with the Knuth–Schroeppel function, and the
+/// factor base holds the primes `p` for which `kN` is a quadratic residue,
+/// found with `jacobi`, together with `sqrt(kN) mod p`. For polynomials
+/// `Q(x) = (ax + b)^2 - kN`, where `a` is a product of factor base primes
+/// and each `a` gives `2^(s - 1)` values of `b`, the values `Q(x) / a` with
+/// `|x| <= M` are sieved with the logarithms of the factor base primes.
+/// Values that are smooth apart from one large prime are kept as well, and
+/// two of them with the same large prime make one relation. Gaussian
+/// elimination over GF(2) then finds products of relations that are squares
+/// on both sides, `X^2 = Y^2 mod n`, and `gcd(X - Y, n)` is usually a factor.
+///
+/// This works best for numbers of 40 to 70 digits; the linear algebra is
+/// dense, which gets slow for the factor bases of larger numbers. `n` should
+/// not be a perfect power. The polynomials are chosen with a fixed seed, so
+/// the result is reproducible.
+///
+/// Returns `None` if `n` is less than 4, or prime, or no factor was found.
+pub fn siqs(n: &BigUint) -> Option<BigUint> {
+    if n < &BigUint::from(4u32) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigUint::from(2u32));
+    }
+
+    let k = choose_multiplier(n);
+    let kn = n * k;
+    let (size, m) = params(kn.bits() as u64);
+    let fb = match FactorBase::new(n, &kn, k, size) {
+        Ok(fb) => fb,
+        Err(p) => {
+            return if &p == n { None } else { Some(p) };
+        }
+    };
+
+    let pmax = u64::from(*fb.primes.last().unwrap());
+    let lp_bound = pmax * LARGE_PRIME_MULTIPLIER;
+    let max_bits = (m as f64).log2() + kn.bits() as f64 / 2.0 - 0.5;
+    let threshold = max_bits - (lp_bound as f64).log2() - SMALL_PRIME_BITS;
+    let siqs = Siqs {
+        n,
+        kn: BigInt::from(kn.clone()),
+        m,
+        m_mod: fb.primes.iter().map(|&p| (m % p as usize) as u32).collect(),
+        threshold: threshold.max(1.0).min(255.0) as u8,
+        lp_bound,
+        target: (LN_2 + ln(&kn)) / 2.0 - (m as f64).ln(),
+        fb,
+    };
+
+    let mut rng = StdRng::from_seed([0; 32]);
+    let mut used = HashSet::new();
+    let mut sieve = vec![0u8; 2 * m];
+    let mut relations = Vec::new();
+    let mut partials = HashMap::new();
+
+    // The sign is column 0, factor base prime j is column j + 1.
+    let columns = siqs.fb.primes.len() + 1;
+    let mut needed = columns + EXTRA_RELATIONS;
+    for _ in 0..MAX_ROUNDS {
+        while relations.len() < needed {
+            let q = siqs.choose_a(&mut rng, &mut used)?;
+            siqs.sieve_a(&q, &mut sieve, &mut relations, &mut partials);
+        }
+
+        for dependency in dependencies(&relations, columns) {
+            if let Some(d) = siqs.square_root(&relations, &dependency) {
+                return Some(d);
+            }
+        }
+        needed = relations.len() + EXTRA_RELATIONS;
+    }
+    None
+}
+
+/// Returns the factor base size and `M` for `kN` of the given size.
+fn params(bits: u64) -> (usize, usize) {
+    let i = match PARAMS.iter().position(|&(b, _, _)| b >= bits) {
+        Some(0) => return (PARAMS[0].1, PARAMS[0].2),
+        Some(i) => i,
+        None => {
+            let (_, size, m) = PARAMS[PARAMS.len() - 1];
+            return (size, m);
+        }
+    };
+
+    let (b0, size0, m0) = PARAMS[i - 1];
+    let (b1, size1, m1) = PARAMS[i];
+    let t = (bits - b0) as usize;
+    let d = (b1 - b0) as usize;
+    (size0 + (size1 - size0) * t / d, m0 + (m1 - m0) * t / d)
+}
+
+/// The natural logarithm of `x`.
+fn ln(x: &BigUint) -> f64 {
+    let shift = x.bits().saturating_sub(64);
+    (x >> shift).to_f64().unwrap().ln() + shift as f64 * LN_2
+}
+
+/// The Legendre symbol `(a / p)` for an odd prime `p`.
+fn legendre(a: u64, p: u64) -> isize {
+    jacobi(&BigInt::from(a), &BigInt::from(p))
+}
+
+/// The inverse of `a` modulo `p`, for `a` coprime to `p`.
+fn inv_mod(a: u64, p: u64) -> u64 {
+    let p = p as i64;
+    (a as i64).extended_gcd(&p).x.mod_floor(&p) as u64
+}
+
+/// Picks the multiplier `k` whose `kN` has the most small primes in its
+/// factor base, weighted by their expected contribution to the sieve, with
+/// the Knuth–Schroeppel function.
+fn choose_multiplier(n: &BigUint) -> u32 {
+    let primes: Vec<(u64, u64)> = PrimeIter::new(3, 1_000)
+        .map(|p| (p, (n % p).to_u64().unwrap()))
+        .collect();
+    let n8 = (n % 8u32).to_u32().unwrap();
+
+    let score = |k: u32| {
+        let mut score = -0.5 * f64::from(k).ln();
+        score += match k * n8 % 8 {
+            1 => 2.0 * LN_2,
+            5 => LN_2,
+            _ => 0.5 * LN_2,
+        };
+        for &(p, r) in &primes {
+            let lnp = (p as f64).ln();
+            if u64::from(k) % p == 0 {
+                score += lnp / p as f64;
+            } else if legendre(u64::from(k) * r % p, p) == 1 {
+                score += 2.0 * lnp / (p - 1) as f64;
+            }
+        }
+        score
+    };
+
+    let mut best = (1, score(1));
+    for &k in &MULTIPLIERS[1..] {
+        let s = score(k);
+        if s > best.1 {
+            best = (k, s);
+        }
+    }
+    best.0
+}
+
+struct FactorBase {
+    /// 2, followed by the odd primes that divide `k` or for which `kN` is a
+    /// quadratic residue.
+    primes: Vec<u32>,
+    /// `sqrt(kN) mod p`, which is zero for the primes dividing `k`.
+    sqrts: Vec<u32>,
+    /// `log2(p)`, rounded.
+    logs: Vec<u8>,
+}
+
+impl FactorBase {
+    /// Collects `size` primes, or returns a prime factor of `n` that was
+    /// found on the way.
+    fn new(n: &BigUint, kn: &BigUint, k: u32, size: usize) -> Result<Self, BigUint> {
+        let mut fb = FactorBase {
+            primes: vec![2],
+            sqrts: vec![1],
+            logs: vec![1],
+        };
+
+        for p in PrimeIter::new(3, u64::from(::std::u32::MAX)) {
+            if fb.primes.len() == size {
+                break;
+            }
+
+            let r = (kn % p).to_u64().unwrap();
+            let sqrt = if (n % p).is_zero() {
+                return Err(BigUint::from(p));
+            } else if r == 0 {
+                0
+            } else if legendre(r, p) == 1 {
+                let root = BigUint::from(r).sqrt_mod_prime(&BigUint::from(p));
+                root.unwrap().to_u32().unwrap()
+            } else {
+                continue;
+            };
+            debug_assert!(sqrt != 0 || u64::from(k) % p == 0);
+
+            fb.primes.push(p as u32);
+            fb.sqrts.push(sqrt);
+            fb.logs.push((p as f64).log2().round() as u8);
+        }
+        Ok(fb)
+    }
+}
+
+/// A relation `y^2 = (-1)^e0 * prod p_j^ej * large^2 mod n`, where the
+/// columns of the sign and the primes are listed once for each factor.
+struct Relation {
+    y: BigUint,
+    columns: Vec<u32>,
+    large: u64,
+}
+
+struct Siqs<'a> {
+    n: &'a BigUint,
+    kn: BigInt,
+    fb: FactorBase,
+    m: usize,
+    /// `M mod p` for each factor base prime.
+    m_mod: Vec<u32>,
+    threshold: u8,
+    lp_bound: u64,
+    /// The natural logarithm of the ideal `a`, `sqrt(2kN) / M`.
+    target: f64,
+}
+
+impl<'a> Siqs<'a> {
+    /// Chooses the factor base indices of the primes of a new `a` close to
+    /// the target, or returns `None` if no unused one was found.
+    fn choose_a<R: Rng>(&self, rng: &mut R, used: &mut HashSet<Vec<usize>>) -> Option<Vec<usize>> {
+        let primes = &self.fb.primes;
+        let lnp = |i: usize| f64::from(primes[i]).ln();
+
+        // The primes of a are neither 2 nor divisors of k, nor too small.
+        let lo = lower_bound(primes, |&p| p < 11);
+        let candidates: Vec<usize> = (lo..primes.len())
+            .filter(|&i| self.fb.sqrts[i] != 0)
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let size = A_PRIME_SIZE.min(primes[primes.len() * 3 / 4]);
+        let s = ((self.target / f64::from(size).ln()).round() as usize).max(1);
+
+        // The first s - 1 primes come from a window around the ideal size.
+        let center = lower_bound(&candidates, |&i| lnp(i) < self.target / s as f64);
+        let width = (2 * s).max(20);
+        let mut window =
+            &candidates[center.saturating_sub(width)..(center + width).min(candidates.len())];
+        if window.len() < s {
+            window = &candidates[..];
+        }
+        if window.len() < s {
+            return None;
+        }
+
+        let mut best: Option<(f64, Vec<usize>)> = None;
+        for attempt in 0..1_000 {
+            let mut q: Vec<usize> = if s == 1 {
+                vec![window[rng.gen_range(0, window.len())]]
+            } else {
+                let mut q: Vec<usize> = index::sample(rng, window.len(), s - 1)
+                    .into_iter()
+                    .map(|i| window[i])
+                    .collect();
+
+                // The last one brings the product closest to the target.
+                let rest = self.target - q.iter().map(|&i| lnp(i)).sum::<f64>();
+                let pos = lower_bound(&candidates, |&i| lnp(i) < rest);
+                let last = candidates[pos.saturating_sub(s)..(pos + s).min(candidates.len())]
+                    .iter()
+                    .filter(|i| !q.contains(i))
+                    .min_by(|&&i, &&j| {
+                        let (ei, ej) = ((lnp(i) - rest).abs(), (lnp(j) - rest).abs());
+                        ei.partial_cmp(&ej).unwrap()
+                    });
+                match last {
+                    Some(&last) => q.push(last),
+                    None => continue,
+                }
+                q
+            };
+            q.sort();
+
+            if !used.contains(&q) {
+                let error = (q.iter().map(|&i| lnp(i)).sum::<f64>() - self.target).abs();
+                if best.as_ref().map_or(true, |b| error < b.0) {
+                    best = Some((error, q));
+                }
+            }
+            if best.is_some() && attempt >= 20 {
+                break;
+            }
+        }
+
+        let (_, q) = best?;
+        used.insert(q.clone());
+        Some(q)
+    }
+
+    /// Sieves all polynomials with the `a` made up of the primes `q`.
+    fn sieve_a(
+        &self,
+        q: &[usize],
+        sieve: &mut [u8],
+        relations: &mut Vec<Relation>,
+        partials: &mut HashMap<u64, Relation>,
+    ) {
+        let fb = &self.fb;
+        let a: BigUint = q.iter().map(|&i| BigUint::from(fb.primes[i])).product();
+
+        // B_l = a / q_l * (t_l * (a / q_l)^-1 mod q_l), so that B_l^2 = kN
+        // mod q_l and B_l = 0 mod the other primes of a. Every b = sum ±B_l
+        // has b^2 = kN mod a.
+        let bs: Vec<BigUint> = q
+            .iter()
+            .map(|&i| {
+                let p = u64::from(fb.primes[i]);
+                let a_l = &a / p;
+                let mut g = u64::from(fb.sqrts[i]) * inv_mod((&a_l % p).to_u64().unwrap(), p) % p;
+                if g > p / 2 {
+                    g = p - g;
+                }
+                a_l * g
+            })
+            .collect();
+
+        // The roots x = a^-1 (±t - b) mod p of Q(x) / a, for the sieved
+        // primes, and 2 B_l a^-1 mod p to move between values of b. A zero
+        // inverse marks the primes that are not sieved.
+        let len = fb.primes.len();
+        let mut ainv = vec![0u64; len];
+        for (j, inv) in ainv.iter_mut().enumerate().skip(1) {
+            let p = u64::from(fb.primes[j]);
+            let am = (&a % p).to_u64().unwrap();
+            if fb.sqrts[j] != 0 && am != 0 {
+                *inv = inv_mod(am, p);
+            }
+        }
+        let bainv2: Vec<Vec<u32>> = bs
+            .iter()
+            .map(|b_l| {
+                (0..len)
+                    .map(|j| {
+                        let p = u64::from(fb.primes[j]);
+                        (2 * (b_l % p).to_u64().unwrap() * ainv[j] % p) as u32
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut b = BigInt::from(bs.iter().sum::<BigUint>());
+        let mut roots = vec![(0u32, 0u32); len];
+        for (j, root) in roots.iter_mut().enumerate().skip(1) {
+            if ainv[j] != 0 {
+                let p = u64::from(fb.primes[j]);
+                let t = u64::from(fb.sqrts[j]);
+                let bm = (magnitude(&b) % p).to_u64().unwrap();
+                *root = (
+                    (ainv[j] * (t + p - bm) % p) as u32,
+                    (ainv[j] * (2 * p - t - bm) % p) as u32,
+                );
+            }
+        }
+
+        let a = BigInt::from(a);
+        for i in 0..1usize << (q.len() - 1) {
+            if i > 0 {
+                // Gray code order: one B_v changes its sign per step.
+                let v = i.trailing_zeros() as usize;
+                let minus = ((i ^ (i >> 1)) >> v) & 1 == 1;
+                let step = BigInt::from(&bs[v] << 1);
+                if minus {
+                    b -= step;
+                } else {
+                    b += step;
+                }
+                for (j, root) in roots.iter_mut().enumerate().skip(1) {
+                    if ainv[j] != 0 {
+                        let p = fb.primes[j];
+                        let d = if minus {
+                            bainv2[v][j]
+                        } else {
+                            p - bainv2[v][j]
+                        };
+                        *root = ((root.0 + d) % p, (root.1 + d) % p);
+                    }
+                }
+            }
+
+            self.sieve_poly(&a, &b, q, &ainv, &roots, sieve, relations, partials);
+        }
+    }
+
+    /// Sieves `Q(x) / a` for `-M <= x < M` and keeps the relations.
+    #[allow(clippy::too_many_arguments)]
+    fn sieve_poly(
+        &self,
+        a: &BigInt,
+        b: &BigInt,
+        q: &[usize],
+        ainv: &[u64],
+        roots: &[(u32, u32)],
+        sieve: &mut [u8],
+        relations: &mut Vec<Relation>,
+        partials: &mut HashMap<u64, Relation>,
+    ) {
+        let fb = &self.fb;
+        let len = fb.primes.len();
+
+        // Position i of the sieve is x = i - M, so the roots move by M.
+        let offsets: Vec<(u32, u32)> = (0..len)
+            .map(|j| {
+                let p = fb.primes[j];
+                let (r1, r2) = roots[j];
+                ((r1 + self.m_mod[j]) % p, (r2 + self.m_mod[j]) % p)
+            })
+            .collect();
+
+        for s in sieve.iter_mut() {
+            *s = 0;
+        }
+        let first = lower_bound(&fb.primes, |&p| p < MIN_SIEVE_PRIME);
+        for j in first..len {
+            if ainv[j] == 0 {
+                continue;
+            }
+            let p = fb.primes[j] as usize;
+            let log = fb.logs[j];
+            let (r1, r2) = offsets[j];
+            let mut i = r1 as usize;
+            while i < sieve.len() {
+                sieve[i] = sieve[i].wrapping_add(log);
+                i += p;
+            }
+            if r2 != r1 {
+                let mut i = r2 as usize;
+                while i < sieve.len() {
+                    sieve[i] = sieve[i].wrapping_add(log);
+                    i += p;
+                }
+            }
+        }
+
+        for (i, &s) in sieve.iter().enumerate() {
+            if s < self.threshold {
+                continue;
+            }
+
+            let x = BigInt::from(i as i64 - self.m as i64);
+            let y = a * &x + b;
+            let value = (&y * &y - &self.kn) / a;
+            if value.is_zero() {
+                continue;
+            }
+
+            // Q(x) = a * value, with a's primes listed first.
+            let mut columns = Vec::new();
+            if value.is_negative() {
+                columns.push(0);
+            }
+            columns.extend(q.iter().map(|&j| j as u32 + 1));
+
+            let mut rest = magnitude(&value).clone();
+            for j in 0..len {
+                let p = fb.primes[j];
+                let divides = if ainv[j] == 0 {
+                    (&rest % p).is_zero()
+                } else {
+                    let r = (i % p as usize) as u32;
+                    r == offsets[j].0 || r == offsets[j].1
+                };
+                if divides {
+                    while (&rest % p).is_zero() {
+                        rest /= p;
+                        columns.push(j as u32 + 1);
+                    }
+                }
+            }
+
+            let relation = Relation {
+                y: magnitude(&y) % self.n,
+                columns,
+                large: 1,
+            };
+            if rest.is_one() {
+                relations.push(relation);
+            } else if let Some(large) = rest.to_u64().filter(|&l| l < self.lp_bound) {
+                // Two relations with the same large prime make a full one.
+                match partials.get(&large) {
+                    Some(other) => {
+                        let mut columns = other.columns.clone();
+                        columns.extend(relation.columns);
+                        relations.push(Relation {
+                            y: &other.y * relation.y % self.n,
+                            columns,
+                            large,
+                        });
+                    }
+                    None => {
+                        partials.insert(large, relation);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Multiplies the relations of a dependency, and returns the factor
+    /// `gcd(X - Y, n)` of the resulting `X^2 = Y^2 mod n`, if nontrivial.
+    fn square_root(&self, relations: &[Relation], dependency: &[usize]) -> Option<BigUint> {
+        let n = self.n;
+        let mut x = BigUint::one();
+        let mut y = BigUint::one();
+        let mut exponents = vec![0u32; self.fb.primes.len() + 1];
+        for &r in dependency {
+            let relation = &relations[r];
+            x = x * &relation.y % n;
+            y = y * relation.large % n;
+            for &c in &relation.columns {
+                exponents[c as usize] += 1;
+            }
+        }
+
+        for (j, &e) in exponents.iter().enumerate().skip(1) {
+            debug_assert!(e % 2 == 0);
+            if e > 0 {
+                let p = BigUint::from(self.fb.primes[j - 1]);
+                y = y * p.modpow(&BigUint::from(e / 2), n) % n;
+            }
+        }
+
+        let diff = if x >= y { x - y } else { y - x };
+        let g = diff.gcd(n);
+        if g.is_one() || &g == n {
+            None
+        } else {
+            Some(g)
+        }
+    }
+}
+
+/// Returns the subsets of the relations whose exponent vectors sum to zero
+/// modulo 2, by Gaussian elimination over GF(2).
+fn dependencies(relations: &[Relation], columns: usize) -> Vec<Vec<usize>> {
+    let rows = relations.len();
+    let words = Integer::div_ceil(&columns, &64);
+    let width = words + Integer::div_ceil(&rows, &64);
+
+    // Each row holds the exponent vector modulo 2, followed by the set of
+    // relations it is the sum of.
+    let mut matrix = vec![0u64; rows * width];
+    for (r, relation) in relations.iter().enumerate() {
+        let row = &mut matrix[r * width..(r + 1) * width];
+        for &c in &relation.columns {
+            row[c as usize / 64] ^= 1 << (c % 64);
+        }
+        row[words + r / 64] |= 1 << (r % 64);
+    }
+
+    let bit =
+        |matrix: &[u64], r: usize, c: usize| (matrix[r * width + c / 64] >> (c % 64)) & 1 == 1;
+    let mut pivot = vec![false; rows];
+    for c in 0..columns {
+        let p = match (0..rows).find(|&r| !pivot[r] && bit(&matrix, r, c)) {
+            Some(p) => p,
+            None => continue,
+        };
+        pivot[p] = true;
+
+        let pivot_row = matrix[p * width..(p + 1) * width].to_vec();
+        for r in 0..rows {
+            if r != p && bit(&matrix, r, c) {
+                for (x, y) in matrix[r * width..(r + 1) * width]
+                    .iter_mut()
+                    .zip(&pivot_row)
+                {
+                    *x ^= y;
+                }
+            }
+        }
+    }
+
+    // The rows that never became a pivot are zero now.
+    (0..rows)
+        .filter(|&r| !pivot[r])
+        .map(|r| {
+            (0..rows)
+                .filter(|&i| bit(&matrix, r, 64 * words + i))
+                .collect()
+        })
+        .collect()
+}
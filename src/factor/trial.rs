@@ -0,0 +1,204 @@
+use num_traits::{One, ToPrimitive, Zero};
+
+use super::Factorization;
+use crate::BigUint;
+
+/// Primes below this bound are kept in `SMALL_PRIMES`.
+pub(crate) const SMALL_PRIME_BOUND: u64 = 1 << 16;
+
+lazy_static! {
+    /// All primes below `SMALL_PRIME_BOUND`.
+    pub(crate) static ref SMALL_PRIMES: Vec<u64> = sieve(SMALL_PRIME_BOUND);
+}
+
+/// Returns all primes `p <= limit`, in increasing order.
+///
+/// Primes below 2^16 come from a table that is generated once, larger
+/// ones from a segmented sieve of Eratosthenes.
+pub fn primes_up_to(limit: u64) -> Vec<u64> {
+    PrimeIter::new(0, limit).collect()
+}
+
+/// Returns the index of the first element of `slice` for which `pred` is
+/// false, where `pred` must be true on a prefix of `slice` and false on the rest.
+pub(crate) fn lower_bound<T, F: Fn(&T) -> bool>(slice: &[T], pred: F) -> usize {
+    let (mut lo, mut hi) = (0, slice.len());
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(&slice[mid]) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+/// Number of integers sieved at once by `PrimeIter`.
+const SEGMENT: u64 = 1 << 18;
+
+/// The primes in `[lo, hi]` in increasing order, from a segmented sieve, so
+/// that long ranges don't need much memory.
+pub(crate) struct PrimeIter {
+    /// The odd primes up to `sqrt(hi)`.
+    base: Vec<u64>,
+    /// The start of the next segment.
+    start: u64,
+    hi: u64,
+    primes: Vec<u64>,
+    pos: usize,
+}
+
+impl PrimeIter {
+    pub(crate) fn new(lo: u64, hi: u64) -> Self {
+        let mut primes = Vec::new();
+        let mut start = lo;
+
+        // The table covers the start of the range.
+        if lo < SMALL_PRIME_BOUND {
+            let begin = lower_bound(&SMALL_PRIMES, |&p| p < lo);
+            let end = lower_bound(&SMALL_PRIMES, |&p| p <= hi);
+            primes = SMALL_PRIMES[begin..::std::cmp::max(begin, end)].to_vec();
+            start = SMALL_PRIME_BOUND;
+        }
+
+        let root = (hi as f64).sqrt() as u64 + 1;
+        let base = if start > hi {
+            Vec::new()
+        } else if root < SMALL_PRIME_BOUND {
+            SMALL_PRIMES[1..].to_vec()
+        } else {
+            sieve(root + 1)[1..].to_vec()
+        };
+
+        PrimeIter {
+            base,
+            start,
+            hi,
+            primes,
+            pos: 0,
+        }
+    }
+
+    /// Sieves the next segment, `[start, start + SEGMENT)`, which starts above 2^16.
+    fn next_segment(&mut self) {
+        let start = self.start;
+        let end = if self.hi - start < SEGMENT {
+            self.hi + 1
+        } else {
+            start + SEGMENT
+        };
+
+        let mut composite = vec![false; (end - start) as usize];
+        for &p in self.base.iter().take_while(|&&p| p * p < end) {
+            let mut m = ::std::cmp::max(p * p, (start + p - 1) / p * p);
+            while m < end {
+                composite[(m - start) as usize] = true;
+                m += p;
+            }
+        }
+
+        self.primes.clear();
+        self.pos = 0;
+        let first_odd = start | 1;
+        self.primes.extend(
+            (first_odd..end)
+                .step_by(2)
+                .filter(|&n| !composite[(n - start) as usize]),
+        );
+        self.start = end;
+    }
+}
+
+impl Iterator for PrimeIter {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while self.pos == self.primes.len() {
+            if self.start > self.hi {
+                return None;
+            }
+            self.next_segment();
+        }
+        self.pos += 1;
+        Some(self.primes[self.pos - 1])
+    }
+}
+
+/// The primes below `bound`.
+fn sieve(bound: u64) -> Vec<u64> {
+    if bound <= 2 {
+        return Vec::new();
+    }
+
+    // composite[i] records whether 2i + 1 is composite.
+    let len = (bound / 2) as usize;
+    let mut composite = vec![false; len];
+    let mut i = 1;
+    while (2 * i + 1) * (2 * i + 1) < bound as usize {
+        if !composite[i] {
+            let p = 2 * i + 1;
+            let mut j = p * p / 2;
+            while j < len {
+                composite[j] = true;
+                j += p;
+            }
+        }
+        i += 1;
+    }
+
+    let mut primes = vec![2];
+    primes.extend(
+        (1..len)
+            .filter(|&i| !composite[i])
+            .map(|i| 2 * i as u64 + 1),
+    );
+    primes
+}
+
+/// Divides out all prime factors `p <= bound` of `n`.
+///
+/// Returns the factorization of the part of `n` made up of these primes,
+/// and the remaining cofactor, which is either one or only has prime
+/// factors larger than `bound`.
+///
+/// Panics if `n` is zero.
+pub fn trial_division(n: &BigUint, bound: u64) -> (Factorization, BigUint) {
+    assert!(!n.is_zero(), "zero has no factorization");
+
+    let mut factors = Factorization::new();
+    let mut rest = n.clone();
+
+    let twos = rest.trailing_zeros().unwrap_or(0);
+    if twos > 0 && bound >= 2 {
+        factors.insert(BigUint::from(2u32), twos as u32);
+        rest >>= twos;
+    }
+
+    let primes = primes_up_to(bound);
+    for &p in primes.iter().skip(1) {
+        if rest.is_one() {
+            break;
+        }
+
+        // Once p^2 exceeds the rest, it is prime itself.
+        if rest.to_u64().map_or(false, |r| r / p < p) {
+            if rest.to_u64().unwrap() <= bound {
+                factors.insert(rest, 1);
+                rest = BigUint::one();
+            }
+            break;
+        }
+
+        let mut e = 0;
+        while (&rest % p).is_zero() {
+            rest /= p;
+            e += 1;
+        }
+        if e > 0 {
+            factors.insert(BigUint::from(p), e);
+        }
+    }
+
+    (factors, rest)
+}
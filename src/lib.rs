@@ -121,7 +121,11 @@ mod macros;
 
 mod bigint;
 mod biguint;
+mod crt;
+mod scratch;
 
+#[cfg(feature = "prime")]
+pub mod factor;
 #[cfg(feature = "prime")]
 pub mod prime;
 
@@ -188,9 +192,14 @@ impl Error for ParseBigIntError {
     }
 }
 
+pub use biguint::BarrettReducer;
 pub use biguint::BigUint;
+pub use biguint::FixedBaseExp;
 pub use biguint::IntoBigUint;
+pub use biguint::MontgomeryContext;
 pub use biguint::ToBigUint;
+pub use crt::{crt, CrtContext};
+pub use scratch::Scratch;
 
 pub use bigint::negate_sign;
 pub use bigint::BigInt;
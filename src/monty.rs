@@ -1,14 +1,13 @@
 #![allow(clippy::many_single_char_names)]
 
+use integer::Integer;
 use num_traits::{One, Zero};
 use std::ops::Shl;
 
+use algorithms::{div_rem_scratch, mac_sqr, montgomery_sqr_threshold};
 use big_digit::{self, BigDigit, DoubleBigDigit, SignedDoubleBigDigit};
 use biguint::BigUint;
-
-struct MontyReducer {
-    n0inv: BigDigit,
-}
+use scratch::Scratch;
 
 // k0 = -m**-1 mod 2**BITS. Algorithm from: Dumas, J.G. "On Newton–Raphson
 // Iteration for Multiplicative Inverses Modulo Prime Powers".
@@ -27,10 +26,672 @@ fn inv_mod_alt(b: BigDigit) -> BigDigit {
     -k0 as BigDigit
 }
 
-impl MontyReducer {
-    fn new(n: &BigUint) -> Self {
-        let n0inv = inv_mod_alt(n.data[0]);
-        MontyReducer { n0inv }
+// Returns the sliding window size to use for an exponent of the given bit
+// length. The thresholds are where the cheaper multiplications of a larger
+// window start to pay for its bigger table.
+pub(super) fn window_size(bits: usize) -> usize {
+    if bits > 1791 {
+        7
+    } else if bits > 671 {
+        6
+    } else if bits > 239 {
+        5
+    } else if bits > 79 {
+        4
+    } else if bits > 23 {
+        3
+    } else {
+        1
+    }
+}
+
+// Returns whether bit i of x is set.
+#[inline]
+pub(super) fn bit(x: &BigUint, i: usize) -> bool {
+    (x.data[i / big_digit::BITS] >> (i % big_digit::BITS)) & 1 == 1
+}
+
+/// A Montgomery multiplication context for a fixed, odd modulus.
+///
+/// Building the context computes `-m^-1 mod 2^BITS` and `R^2 mod m` once
+/// (where `R = 2^(BITS * len(m))`), so repeated multiplications and
+/// exponentiations modulo the same `m` skip that setup, including the full
+/// division needed for `R^2 mod m`.
+///
+/// Values in Montgomery form are plain `BigUint`s smaller than the modulus;
+/// use `to_montgomery` and `from_montgomery` to convert.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint_dig::{BigUint, MontgomeryContext};
+///
+/// let m = BigUint::from(1_000_000_007u32);
+/// let ctx = MontgomeryContext::new(&m);
+///
+/// let base = BigUint::from(12345u32);
+/// let exp = BigUint::from(65537u32);
+/// assert_eq!(ctx.modpow(&base, &exp), base.modpow(&exp, &m));
+///
+/// let a = ctx.to_montgomery(&BigUint::from(6u32));
+/// let b = ctx.to_montgomery(&BigUint::from(7u32));
+/// assert_eq!(ctx.from_montgomery(&ctx.mul(&a, &b)), BigUint::from(42u32));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MontgomeryContext {
+    modulus: BigUint,
+    n0inv: BigDigit,
+    // rr = 2**(2*_W*len(m)) mod m, padded to len(m) words
+    rr: BigUint,
+    num_words: usize,
+}
+
+impl MontgomeryContext {
+    /// Creates a new context for the given modulus.
+    ///
+    /// Panics if the modulus is even (which includes zero).
+    pub fn new(modulus: &BigUint) -> Self {
+        assert!(modulus.is_odd(), "the modulus must be odd");
+        let num_words = modulus.data.len();
+
+        let mut rr = BigUint::one().shl(2 * num_words * big_digit::BITS) % modulus;
+        rr.data.resize(num_words, 0);
+
+        MontgomeryContext {
+            modulus: modulus.clone(),
+            n0inv: inv_mod_alt(modulus.data[0]),
+            rr,
+            num_words,
+        }
+    }
+
+    /// Returns the modulus of this context.
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    /// Converts `x` into Montgomery form, `x * R mod m`.
+    pub fn to_montgomery(&self, x: &BigUint) -> BigUint {
+        let x = self.pad(x);
+        let mut z = BigUint::zero();
+        montgomery(
+            &mut z,
+            &x,
+            &self.rr,
+            &self.modulus,
+            self.n0inv,
+            self.num_words,
+        );
+        self.reduce(z)
+    }
+
+    /// Converts `x` out of Montgomery form, `x * R^-1 mod m`.
+    pub fn from_montgomery(&self, x: &BigUint) -> BigUint {
+        let x = self.pad(x);
+        let mut z = BigUint::zero();
+        montgomery(
+            &mut z,
+            &x,
+            &self.one(),
+            &self.modulus,
+            self.n0inv,
+            self.num_words,
+        );
+        self.reduce(z)
+    }
+
+    /// Multiplies two values in Montgomery form, returning `a * b * R^-1 mod m`,
+    /// which is again in Montgomery form.
+    pub fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let a = self.pad(a);
+        let b = self.pad(b);
+        let mut z = BigUint::zero();
+        montgomery(&mut z, &a, &b, &self.modulus, self.n0inv, self.num_words);
+        self.reduce(z)
+    }
+
+    /// Returns `x ** y mod m`. `x` and the result are in regular (not
+    /// Montgomery) form.
+    ///
+    /// This uses a sliding window whose size is picked from the bit length of
+    /// `y`, so only odd powers of `x` are precomputed and runs of zero bits in
+    /// the exponent cost a single squaring per bit. Small exponents such as
+    /// 65537 use a plain square-and-multiply without any table.
+    ///
+    /// The running time depends on the value of `y`; use `modpow_ct` for
+    /// secret exponents.
+    pub fn modpow(&self, x: &BigUint, y: &BigUint) -> BigUint {
+        if y.is_zero() {
+            return BigUint::one() % &self.modulus;
+        }
+
+        // We want the lengths of x and m to be equal.
+        // It is OK if x >= m as long as len(x) == len(m).
+        let x = self.pad(x);
+
+        let w = window_size(y.bits());
+        let mut powers = vec![BigUint::zero(); 1 << (w - 1)];
+        let mut z = BigUint::zero();
+        let mut zz = BigUint::zero();
+
+        self.odd_powers(&mut powers, &x, &mut z, montgomery_sqr);
+        self.sliding_window(y, &powers, &mut z, &mut zz, montgomery_sqr);
+
+        // convert to regular number
+        montgomery(
+            &mut zz,
+            &z,
+            &self.one(),
+            &self.modulus,
+            self.n0inv,
+            self.num_words,
+        );
+        self.reduce(zz)
+    }
+
+    /// Sets `out` to `x ** y mod m`, like `modpow`, but keeps all temporary values
+    /// (including the table of powers) in `scratch`.
+    ///
+    /// Once `out` and `scratch` have grown large enough, this doesn't allocate. To that end
+    /// the squarings are done with interleaved Montgomery multiplications, and a base longer
+    /// than the modulus is reduced with `BigUint::div_rem_into`.
+    pub fn modpow_into(&self, out: &mut BigUint, x: &BigUint, y: &BigUint, scratch: &mut Scratch) {
+        let n = self.num_words;
+
+        out.data.clear();
+        if y.is_zero() {
+            if !self.modulus.is_one() {
+                out.data.push(1);
+            }
+            return;
+        }
+
+        let w = window_size(y.bits());
+        let Scratch {
+            ref mut digits,
+            ref mut nums,
+        } = *scratch;
+        if nums.len() < 4 + (1 << (w - 1)) {
+            nums.resize(4 + (1 << (w - 1)), BigUint::zero());
+        }
+        let (tmp, powers) = nums.split_at_mut(4);
+        let powers = &mut powers[..1 << (w - 1)];
+        let (x_pad, tmp) = tmp.split_first_mut().unwrap();
+        let (one, tmp) = tmp.split_first_mut().unwrap();
+        let (z, tmp) = tmp.split_first_mut().unwrap();
+        let zz = &mut tmp[0];
+
+        if x.data.len() > n {
+            div_rem_scratch(zz, x_pad, x, &self.modulus, digits);
+        } else {
+            x_pad.data.clear();
+            x_pad.data.extend_from_slice(&x.data);
+        }
+        x_pad.data.resize(n, 0);
+
+        self.odd_powers(powers, x_pad, z, montgomery_sqr_interleaved);
+        self.sliding_window(y, powers, z, zz, montgomery_sqr_interleaved);
+
+        // convert to regular number
+        one.data.clear();
+        one.data.push(1);
+        one.data.resize(n, 0);
+        montgomery(zz, z, one, &self.modulus, self.n0inv, n);
+
+        let r = self.reduce(::std::mem::replace(zz, BigUint::zero()));
+        out.data.extend_from_slice(&r.data);
+        *zz = r;
+    }
+
+    /// Returns the product of `x ** y mod m` over all `(x, y)` in `pairs`. The bases and the
+    /// result are in regular form; an empty product is 1.
+    ///
+    /// This is Straus' simultaneous exponentiation: every base gets its own sliding window
+    /// table, but the squarings are shared, so e.g. `g^a * h^b` costs little more than one
+    /// `modpow` with the longer exponent, instead of two.
+    ///
+    /// The running time depends on the values of the exponents.
+    pub fn multi_modpow(&self, pairs: &[(&BigUint, &BigUint)]) -> BigUint {
+        let m = &self.modulus;
+        let k = self.n0inv;
+        let num_words = self.num_words;
+
+        let mut z = BigUint::zero();
+        let mut zz = BigUint::zero();
+
+        // The windows of all exponents as (lowest bit, base, index into the table of the base).
+        let mut tables = Vec::with_capacity(pairs.len());
+        let mut windows = Vec::new();
+        for &(x, y) in pairs {
+            if y.is_zero() {
+                continue;
+            }
+
+            let w = window_size(y.bits());
+            let mut powers = vec![BigUint::zero(); 1 << (w - 1)];
+            self.odd_powers(&mut powers, &self.pad(x), &mut z, montgomery_sqr);
+
+            let mut i = y.bits();
+            while i > 0 {
+                if !bit(y, i - 1) {
+                    i -= 1;
+                    continue;
+                }
+
+                // Same windows as in `sliding_window`.
+                let mut l = i.saturating_sub(w);
+                while !bit(y, l) {
+                    l += 1;
+                }
+                let mut window = 0;
+                for j in (l..i).rev() {
+                    window = (window << 1) | bit(y, j) as usize;
+                }
+                windows.push((l, tables.len(), window >> 1));
+                i = l;
+            }
+            tables.push(powers);
+        }
+
+        if windows.is_empty() {
+            return BigUint::one() % m;
+        }
+        windows.sort_unstable_by_key(|&(l, _, _)| ::std::cmp::Reverse(l));
+
+        // z holds the product of the powers of all windows so far, each shifted down to bit i.
+        let (mut i, t, idx) = windows[0];
+        z.data.clear();
+        z.data.extend_from_slice(&tables[t][idx].data);
+        for &(l, t, idx) in &windows[1..] {
+            for _ in l..i {
+                montgomery_sqr(&mut zz, &z, m, k, num_words);
+                ::std::mem::swap(&mut z, &mut zz);
+            }
+            montgomery(&mut zz, &z, &tables[t][idx], m, k, num_words);
+            ::std::mem::swap(&mut z, &mut zz);
+            i = l;
+        }
+        for _ in 0..i {
+            montgomery_sqr(&mut zz, &z, m, k, num_words);
+            ::std::mem::swap(&mut z, &mut zz);
+        }
+
+        // convert to regular number
+        montgomery(&mut zz, &z, &self.one(), m, k, num_words);
+        self.reduce(zz)
+    }
+
+    /// Sets `powers[i]` to `x^(2*i + 1)` in Montgomery form, for `x` padded to the length of
+    /// the modulus. `x2` is used as a temporary.
+    fn odd_powers(&self, powers: &mut [BigUint], x: &BigUint, x2: &mut BigUint, sqr: Sqr) {
+        let m = &self.modulus;
+        let k = self.n0inv;
+        let num_words = self.num_words;
+
+        montgomery(&mut powers[0], x, &self.rr, m, k, num_words);
+        if powers.len() > 1 {
+            sqr(x2, &powers[0], m, k, num_words);
+            for i in 1..powers.len() {
+                let (done, rest) = powers.split_at_mut(i);
+                montgomery(&mut rest[0], &done[i - 1], x2, m, k, num_words);
+            }
+        }
+    }
+
+    /// Sets `z` to `x^y` in Montgomery form, for a nonzero `y` and the odd powers of x from
+    /// `odd_powers`, whose number determines the window size. `zz` is used as a temporary.
+    fn sliding_window(
+        &self,
+        y: &BigUint,
+        powers: &[BigUint],
+        z: &mut BigUint,
+        zz: &mut BigUint,
+        sqr: Sqr,
+    ) {
+        let m = &self.modulus;
+        let k = self.n0inv;
+        let num_words = self.num_words;
+        let w = powers.len().trailing_zeros() as usize + 1;
+
+        let mut started = false;
+
+        // i is the number of exponent bits not consumed yet
+        let mut i = y.bits();
+        while i > 0 {
+            if !bit(y, i - 1) {
+                sqr(zz, z, m, k, num_words);
+                ::std::mem::swap(z, zz);
+                i -= 1;
+                continue;
+            }
+
+            // The longest window of at most w bits, starting at bit i - 1 and
+            // ending in a one bit.
+            let mut l = i.saturating_sub(w);
+            while !bit(y, l) {
+                l += 1;
+            }
+            let mut window = 0;
+            for j in (l..i).rev() {
+                window = (window << 1) | bit(y, j) as usize;
+            }
+
+            if started {
+                for _ in l..i {
+                    sqr(zz, z, m, k, num_words);
+                    ::std::mem::swap(z, zz);
+                }
+                montgomery(zz, z, &powers[window >> 1], m, k, num_words);
+                ::std::mem::swap(z, zz);
+            } else {
+                z.data.clear();
+                z.data.extend_from_slice(&powers[window >> 1].data);
+                started = true;
+            }
+            i = l;
+        }
+    }
+
+    /// Returns `x ** y mod m`, like `modpow`, but in a way that is intended to be
+    /// safe for secret exponents.
+    ///
+    /// The exponent is consumed in fixed 4-bit windows over all of its words,
+    /// table entries are selected by scanning the whole table, and the
+    /// Montgomery reductions end in a branch-free subtraction. The running time
+    /// and memory access pattern therefore only depend on the lengths of the
+    /// modulus and the exponent, not on their values.
+    ///
+    /// If `x` has more words than the modulus it is first reduced with a regular
+    /// (variable time) division; pass a reduced base to avoid this.
+    pub fn modpow_ct(&self, x: &BigUint, y: &BigUint) -> BigUint {
+        let m = &self.modulus;
+        let k = self.n0inv;
+        let num_words = self.num_words;
+
+        let x = self.pad(x);
+        let one = self.one();
+
+        let n = 4;
+        // powers[i] contains x^i, fully reduced
+        let mut powers = Vec::with_capacity(1 << n);
+
+        let mut v1 = BigUint::zero();
+        montgomery_ct(&mut v1, &one, &self.rr, m, k, num_words);
+        powers.push(v1);
+        let mut v2 = BigUint::zero();
+        montgomery_ct(&mut v2, &x, &self.rr, m, k, num_words);
+        powers.push(v2);
+        for i in 2..1 << n {
+            let mut r = BigUint::zero();
+            montgomery_ct(&mut r, &powers[i - 1], &powers[1], m, k, num_words);
+            powers.push(r);
+        }
+
+        let mut z = powers[0].clone();
+        let mut zz = BigUint::zero();
+        let mut p = BigUint::zero();
+        p.data.resize(num_words, 0);
+
+        // Unlike `modpow`, every window is processed the same way, including
+        // the leading ones and windows that are zero.
+        for i in (0..y.data.len()).rev() {
+            let mut yi = y.data[i];
+            let mut j = 0;
+            while j < big_digit::BITS {
+                montgomery_ct(&mut zz, &z, &z, m, k, num_words);
+                montgomery_ct(&mut z, &zz, &zz, m, k, num_words);
+                montgomery_ct(&mut zz, &z, &z, m, k, num_words);
+                montgomery_ct(&mut z, &zz, &zz, m, k, num_words);
+
+                ct_select(&mut p, &powers, yi >> (big_digit::BITS - n));
+                montgomery_ct(&mut zz, &z, &p, m, k, num_words);
+                ::std::mem::swap(&mut z, &mut zz);
+                yi <<= n;
+                j += n;
+            }
+        }
+
+        // convert to regular number
+        montgomery_ct(&mut zz, &z, &one, m, k, num_words);
+        zz.normalize();
+        zz
+    }
+
+    /// Returns `x` with the same number of words as the modulus.
+    /// `x` is only reduced if it is longer than the modulus.
+    fn pad(&self, x: &BigUint) -> BigUint {
+        let mut x = if x.data.len() > self.num_words {
+            // Note: now len(x) <= num_words, not guaranteed ==.
+            x % &self.modulus
+        } else {
+            x.clone()
+        };
+        x.data.resize(self.num_words, 0);
+        x
+    }
+
+    /// Returns 1 with the same number of words as the modulus.
+    fn one(&self) -> BigUint {
+        let mut one = BigUint::one();
+        one.data.resize(self.num_words, 0);
+        one
+    }
+
+    /// Normalizes the output of `montgomery` and reduces it below the modulus.
+    fn reduce(&self, mut z: BigUint) -> BigUint {
+        let m = &self.modulus;
+
+        z.normalize();
+        // One last reduction, just in case.
+        // See golang.org/issue/13907.
+        if &z >= m {
+            // Common case is m has high bit set; in that case,
+            // since z is the same length as m, there can be just
+            // one multiple of m to remove. Just subtract.
+            // We think that the subtract should be sufficient in general,
+            // so do that unconditionally, but double-check,
+            // in case our beliefs are wrong.
+            // The div is not expected to be reached.
+            z -= m;
+            if &z >= m {
+                z %= m;
+            }
+        }
+
+        z.normalize();
+        z
+    }
+}
+
+/// Exponentiation of a fixed base modulo a fixed, odd modulus, with a table
+/// that is precomputed once (the Lim-Lee comb method, HAC algorithm 14.117 and
+/// note 14.121).
+///
+/// The exponent bits are split into `teeth` rows of `a` bits each, and every
+/// row into `tables` blocks of `b = a / tables` columns. For each block the
+/// table contains the products of `g^(2^(row * a + block * b))` over all
+/// `2^teeth` subsets of the rows. An exponentiation then takes `b` squarings
+/// and `a` multiplications, e.g. with the defaults and a 2048 bit exponent
+/// about 200 squarings and 400 multiplications instead of the 2048 squarings
+/// and ~300 multiplications of `modpow`. The table holds `tables * 2^teeth`
+/// numbers of the size of the modulus.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint_dig::{BigUint, FixedBaseExp};
+///
+/// let g = BigUint::from(5u32);
+/// let m = BigUint::from(1_000_000_007u32);
+/// let exp = FixedBaseExp::new(&g, &m, 64);
+///
+/// for &e in &[0u64, 1, 65537, 0xdead_beef_cafe] {
+///     let e = BigUint::from(e);
+///     assert_eq!(exp.modpow(&e), g.modpow(&e, &m));
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FixedBaseExp {
+    ctx: MontgomeryContext,
+    base: BigUint,
+    max_bits: usize,
+    teeth: usize,
+    // bits per row, and columns per block
+    row_bits: usize,
+    cols: usize,
+    // table[block << teeth | j] is the product of g^(2^(i * row_bits + block * cols))
+    // over the set bits i of j, in Montgomery form
+    table: Vec<BigUint>,
+}
+
+impl FixedBaseExp {
+    /// Creates a table for powers of `g` modulo `m` with exponents of up to
+    /// `max_exponent_bits` bits, with 5 teeth and 2 tables, so 64 entries.
+    ///
+    /// Panics if the modulus is even.
+    pub fn new(g: &BigUint, m: &BigUint, max_exponent_bits: usize) -> Self {
+        FixedBaseExp::with_table_size(g, m, max_exponent_bits, 5, 2)
+    }
+
+    /// Creates a table with the given number of teeth and tables, so with
+    /// `tables * 2^teeth` entries. More teeth make each exponentiation cheaper
+    /// in both squarings and multiplications, more tables only save squarings.
+    ///
+    /// Panics if the modulus is even, if `teeth` is not in `1..=20` or if
+    /// `tables` is zero.
+    pub fn with_table_size(
+        g: &BigUint,
+        m: &BigUint,
+        max_exponent_bits: usize,
+        teeth: usize,
+        tables: usize,
+    ) -> Self {
+        assert!(teeth >= 1 && teeth <= 20, "teeth must be in 1..=20");
+        assert!(tables >= 1, "there must be at least one table");
+
+        let ctx = MontgomeryContext::new(m);
+        let modulus = &ctx.modulus;
+        let k = ctx.n0inv;
+        let n = ctx.num_words;
+
+        let cols = Integer::div_ceil(
+            &Integer::div_ceil(&max_exponent_bits.max(1), &teeth),
+            &tables,
+        );
+        let row_bits = cols * tables;
+
+        // g^(2^(i * row_bits + block * cols)), at index i * tables + block
+        let mut g_pow = ctx.to_montgomery(g);
+        g_pow.data.resize(n, 0);
+        let mut tmp = BigUint::zero();
+        let mut teeth_pows = Vec::with_capacity(teeth * tables);
+        for _ in 0..teeth * tables {
+            teeth_pows.push(g_pow.clone());
+            for _ in 0..cols {
+                montgomery_sqr(&mut tmp, &g_pow, modulus, k, n);
+                ::std::mem::swap(&mut g_pow, &mut tmp);
+            }
+        }
+
+        let mut table = Vec::with_capacity(tables << teeth);
+        for block in 0..tables {
+            let mut one = ctx.to_montgomery(&BigUint::one());
+            one.data.resize(n, 0);
+            table.push(one);
+            for j in 1usize..1 << teeth {
+                // add the lowest row of j to the entry without it
+                let i = j.trailing_zeros() as usize;
+                let rest = j & (j - 1);
+                let mut z = BigUint::zero();
+                let row_pow = &teeth_pows[i * tables + block];
+                montgomery(
+                    &mut z,
+                    &table[block << teeth | rest],
+                    row_pow,
+                    modulus,
+                    k,
+                    n,
+                );
+                table.push(z);
+            }
+        }
+
+        FixedBaseExp {
+            base: g.clone(),
+            ctx,
+            max_bits: max_exponent_bits,
+            teeth,
+            row_bits,
+            cols,
+            table,
+        }
+    }
+
+    /// Returns the modulus.
+    pub fn modulus(&self) -> &BigUint {
+        &self.ctx.modulus
+    }
+
+    /// Returns the largest exponent bit length that uses the table.
+    pub fn max_exponent_bits(&self) -> usize {
+        self.max_bits
+    }
+
+    /// Returns `g ** e mod m`.
+    ///
+    /// Exponents with more than `max_exponent_bits` bits are passed on to
+    /// `MontgomeryContext::modpow`. The running time depends on the value of
+    /// `e`.
+    pub fn modpow(&self, e: &BigUint) -> BigUint {
+        let bits = e.bits();
+        if bits > self.max_bits {
+            return self.ctx.modpow(&self.base, e);
+        }
+
+        let m = &self.ctx.modulus;
+        let k = self.ctx.n0inv;
+        let n = self.ctx.num_words;
+        let tables = self.row_bits / self.cols;
+
+        let mut z = BigUint::zero();
+        let mut zz = BigUint::zero();
+        let mut started = false;
+
+        for col in (0..self.cols).rev() {
+            if started {
+                montgomery_sqr(&mut zz, &z, m, k, n);
+                ::std::mem::swap(&mut z, &mut zz);
+            }
+
+            for block in (0..tables).rev() {
+                let mut idx = 0;
+                for i in (0..self.teeth).rev() {
+                    let pos = i * self.row_bits + block * self.cols + col;
+                    idx = (idx << 1) | (pos < bits && bit(e, pos)) as usize;
+                }
+                if idx == 0 {
+                    continue;
+                }
+
+                let entry = &self.table[block << self.teeth | idx];
+                if started {
+                    montgomery(&mut zz, &z, entry, m, k, n);
+                    ::std::mem::swap(&mut z, &mut zz);
+                } else {
+                    z.data.clear();
+                    z.data.extend_from_slice(&entry.data);
+                    started = true;
+                }
+            }
+        }
+
+        if !started {
+            return BigUint::one() % m;
+        }
+
+        // convert to regular number
+        montgomery(&mut zz, &z, &self.ctx.one(), m, k, n);
+        self.ctx.reduce(zz)
     }
 }
 
@@ -80,6 +741,108 @@ fn montgomery(z: &mut BigUint, x: &BigUint, y: &BigUint, m: &BigUint, k: BigDigi
     z.data.truncate(n);
 }
 
+/// A Montgomery squaring, `montgomery_sqr` or `montgomery_sqr_interleaved`.
+type Sqr = fn(&mut BigUint, &BigUint, &BigUint, BigDigit, usize);
+
+/// `montgomery(z, x, x, m, k, n)`, which unlike `montgomery_sqr` never allocates.
+fn montgomery_sqr_interleaved(z: &mut BigUint, x: &BigUint, m: &BigUint, k: BigDigit, n: usize) {
+    montgomery(z, x, x, m, k, n)
+}
+
+/// Like `montgomery(z, x, x, m, k, n)`, but first computes the full square of x with the
+/// squaring algorithms, which need about half the digit multiplications, and then reduces it.
+fn montgomery_sqr(z: &mut BigUint, x: &BigUint, m: &BigUint, k: BigDigit, n: usize) {
+    assert!(
+        x.data.len() == n && m.data.len() == n,
+        "{:?} {:?} {}",
+        x,
+        m,
+        n
+    );
+
+    // For small moduli the separate reduction pass costs more than the squaring saves.
+    if n < montgomery_sqr_threshold() {
+        return montgomery(z, x, x, m, k, n);
+    }
+
+    z.data.clear();
+    z.data.resize(n * 2 + 1, 0);
+    mac_sqr(&mut z.data[..], &x.data);
+    z.data.truncate(n * 2);
+
+    let mut c: BigDigit = 0;
+
+    for i in 0..n {
+        let t = z.data[i].wrapping_mul(k);
+        let c2 = add_mul_vvw(&mut z.data[i..n + i], &m.data, t);
+        let cx = c.wrapping_add(c2);
+        let cy = cx.wrapping_add(z.data[n + i]);
+        z.data[n + i] = cy;
+        c = if cx < c2 || cy < cx { 1 } else { 0 };
+    }
+
+    if c == 0 {
+        let (first, second) = z.data.split_at_mut(n);
+        first.swap_with_slice(&mut second[..]);
+    } else {
+        let (first, second) = z.data.split_at_mut(n);
+        sub_vv(first, second, &m.data);
+    }
+    z.data.truncate(n);
+}
+
+/// Like `montgomery`, but computes a fully reduced result in constant time:
+/// the carries and the final subtraction of m are done without branching on
+/// the data, so the running time only depends on n.
+///
+/// x and y are required to satisfy 0 <= x, y < 2**(n*_W) and x * y < m * 2**(n*_W),
+/// which holds in particular if either of them is < m. The result z satisfies z < m.
+fn montgomery_ct(z: &mut BigUint, x: &BigUint, y: &BigUint, m: &BigUint, k: BigDigit, n: usize) {
+    assert!(x.data.len() == n && y.data.len() == n && m.data.len() == n);
+
+    // z[..2n] holds the product, z[2n..] the product minus m.
+    z.data.clear();
+    z.data.resize(n * 3, 0);
+
+    let mut c: BigDigit = 0;
+
+    for i in 0..n {
+        let c2 = add_mul_vvw(&mut z.data[i..n + i], &x.data, y.data[i]);
+        let t = z.data[i].wrapping_mul(k);
+        let c3 = add_mul_vvw(&mut z.data[i..n + i], &m.data, t);
+        let cx = c.wrapping_add(c2);
+        let cy = cx.wrapping_add(c3);
+        z.data[n + i] = cy;
+        c = ((cx < c2) | (cy < c3)) as BigDigit;
+    }
+
+    // The value is c * 2**(n*_W) + z[n..2n] < 2m. It is >= m exactly if there
+    // was a carry out, or if subtracting m does not borrow.
+    let (low, high) = z.data.split_at_mut(n * 2);
+    let b = sub_vv(high, &low[n..], &m.data);
+    let mask = (c | (b ^ 1)).wrapping_neg();
+    for i in 0..n {
+        low[i] = (high[i] & mask) | (low[n + i] & !mask);
+    }
+    z.data.truncate(n);
+}
+
+/// Copies `table[idx]` into `z`, touching every entry of the table so that
+/// the memory access pattern does not depend on `idx`.
+fn ct_select(z: &mut BigUint, table: &[BigUint], idx: BigDigit) {
+    for zi in z.data.iter_mut() {
+        *zi = 0;
+    }
+    for (i, entry) in table.iter().enumerate() {
+        // mask is all ones if i == idx and zero otherwise
+        let d = i as BigDigit ^ idx;
+        let mask = (((d | d.wrapping_neg()) >> (big_digit::BITS - 1)) ^ 1).wrapping_neg();
+        for (zi, ei) in z.data.iter_mut().zip(entry.data.iter()) {
+            *zi |= ei & mask;
+        }
+    }
+}
+
 #[inline]
 fn add_mul_vvw(z: &mut [BigDigit], x: &[BigDigit], y: BigDigit) -> BigDigit {
     let mut c = 0;
@@ -112,7 +875,7 @@ fn sub_vv(z: &mut [BigDigit], x: &[BigDigit], y: &[BigDigit]) -> BigDigit {
 fn add_ww(x: BigDigit, y: BigDigit, c: BigDigit) -> (BigDigit, BigDigit) {
     let yc = y.wrapping_add(c);
     let z0 = x.wrapping_add(yc);
-    let z1 = if z0 < x || yc < y { 1 } else { 0 };
+    let z1 = ((z0 < x) | (yc < y)) as BigDigit;
 
     (z1, z0)
 }
@@ -124,101 +887,3 @@ fn mul_add_www(x: BigDigit, y: BigDigit, c: BigDigit) -> (BigDigit, BigDigit) {
     ((z >> big_digit::BITS) as BigDigit, z as BigDigit)
 }
 
-/// Calculates x ** y mod m using a fixed, 4-bit window.
-pub fn monty_modpow(x: &BigUint, y: &BigUint, m: &BigUint) -> BigUint {
-    assert!(m.data[0] & 1 == 1);
-    let mr = MontyReducer::new(m);
-    let num_words = m.data.len();
-
-    let mut x = x.clone();
-
-    // We want the lengths of x and m to be equal.
-    // It is OK if x >= m as long as len(x) == len(m).
-    if x.data.len() > num_words {
-        x %= m;
-        // Note: now len(x) <= numWords, not guaranteed ==.
-    }
-    if x.data.len() < num_words {
-        x.data.resize(num_words, 0);
-    }
-
-    // rr = 2**(2*_W*len(m)) mod m
-    let mut rr = BigUint::one();
-    rr = (rr.shl(2 * num_words * big_digit::BITS)) % m;
-    if rr.data.len() < num_words {
-        rr.data.resize(num_words, 0);
-    }
-    // one = 1, with equal length to that of m
-    let mut one = BigUint::one();
-    one.data.resize(num_words, 0);
-
-    let n = 4;
-    // powers[i] contains x^i
-    let mut powers = Vec::with_capacity(1 << n);
-
-    let mut v1 = BigUint::zero();
-    montgomery(&mut v1, &one, &rr, m, mr.n0inv, num_words);
-    powers.push(v1);
-    let mut v2 = BigUint::zero();
-    montgomery(&mut v2, &x, &rr, m, mr.n0inv, num_words);
-    powers.push(v2);
-    for i in 2..1 << n {
-        let mut r = BigUint::zero();
-        montgomery(&mut r, &powers[i - 1], &powers[1], m, mr.n0inv, num_words);
-        powers.push(r);
-    }
-
-    // initialize z = 1 (Montgomery 1)
-    let mut z = powers[0].clone();
-    z.data.resize(num_words, 0);
-    let mut zz = BigUint::zero();
-    zz.data.resize(num_words, 0);
-
-    // same windowed exponent, but with Montgomery multiplications
-    for i in (0..y.data.len()).rev() {
-        let mut yi = y.data[i];
-        let mut j = 0;
-        while j < big_digit::BITS {
-            if i != y.data.len() - 1 || j != 0 {
-                montgomery(&mut zz, &z, &z, m, mr.n0inv, num_words);
-                montgomery(&mut z, &zz, &zz, m, mr.n0inv, num_words);
-                montgomery(&mut zz, &z, &z, m, mr.n0inv, num_words);
-                montgomery(&mut z, &zz, &zz, m, mr.n0inv, num_words);
-            }
-            montgomery(
-                &mut zz,
-                &z,
-                &powers[(yi >> (big_digit::BITS - n)) as usize],
-                m,
-                mr.n0inv,
-                num_words,
-            );
-            ::std::mem::swap(&mut z, &mut zz);
-            yi <<= n;
-            j += n;
-        }
-    }
-
-    // convert to regular number
-    montgomery(&mut zz, &z, &one, m, mr.n0inv, num_words);
-
-    zz.normalize();
-    // One last reduction, just in case.
-    // See golang.org/issue/13907.
-    if &zz >= m {
-        // Common case is m has high bit set; in that case,
-        // since zz is the same length as m, there can be just
-        // one multiple of m to remove. Just subtract.
-        // We think that the subtract should be sufficient in general,
-        // so do that unconditionally, but double-check,
-        // in case our beliefs are wrong.
-        // The div is not expected to be reached.
-        zz -= m;
-        if &zz >= m {
-            zz %= m;
-        }
-    }
-
-    zz.normalize();
-    zz
-}
@@ -5,7 +5,7 @@ use byteorder::{BigEndian, ByteOrder};
 use integer::Integer;
 use num_traits::{FromPrimitive, One, ToPrimitive, Zero};
 use rand::rngs::StdRng;
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
 
 use crate::algorithms::jacobi;
 use crate::big_digit;
@@ -51,25 +51,57 @@ const PRIME_BIT_MASK: u64 = 1 << 2
 /// If x is chosen randomly and not prime, ProbablyPrime probably returns false.
 /// The probability of returning true for a randomly chosen non-prime is at most ¼ⁿ.
 ///
-/// ProbablyPrime is 100% accurate for inputs less than 2⁶⁴.
+/// ProbablyPrime is 100% accurate for inputs less than 2⁶⁴, where only the
+/// Baillie-PSW test is applied and n is ignored.
 /// See Menezes et al., Handbook of Applied Cryptography, 1997, pp. 145-149,
 /// and FIPS 186-4 Appendix F for further discussion of the error probabilities.
 ///
 /// ProbablyPrime is not suitable for judging primes that an adversary may
-/// have crafted to fool the test.
+/// have crafted to fool the test: the bases are derived from x, so they are
+/// known in advance. Use `probably_prime_with_rng` for such inputs.
+/// `probably_prime_with_policy` picks the number of rounds from the size of x
+/// instead.
 ///
 /// This is a port of `ProbablyPrime` from the go std lib.
 pub fn probably_prime(x: &BigUint, n: usize) -> bool {
+    if let Some(prime) = check_small_factors(x) {
+        return prime;
+    }
+
+    let n = if x.bits() <= 64 { 0 } else { n };
+    probably_prime_miller_rabin(x, n + 1, true) && probably_prime_lucas(x)
+}
+
+/// Reports whether x is probably prime, like `probably_prime`, but with the
+/// n bases of the Miller-Rabin test drawn from `rng`.
+///
+/// With an unpredictable `rng`, such as `rand::rngs::OsRng`, the probability
+/// of returning true for a non-prime is at most ¼ⁿ even if x was chosen by an
+/// adversary, on top of the Baillie-PSW test, which has no known
+/// counterexamples.
+pub fn probably_prime_with_rng<R: Rng + ?Sized>(x: &BigUint, n: usize, rng: &mut R) -> bool {
+    if let Some(prime) = check_small_factors(x) {
+        return prime;
+    }
+
+    let n = if x.bits() <= 64 { 0 } else { n };
+    miller_rabin_with_rng(x, n + 1, true, rng) && probably_prime_lucas(x)
+}
+
+/// Decides whether x is prime if x is below 64 or divisible by one of the
+/// small primes checked here, and returns `None` if the Miller-Rabin and Lucas
+/// tests are needed.
+fn check_small_factors(x: &BigUint) -> Option<bool> {
     if x.is_zero() {
-        return false;
+        return Some(false);
     }
 
     if x < &*BIG_64 {
-        return (PRIME_BIT_MASK & (1 << x.to_u64().unwrap())) != 0;
+        return Some((PRIME_BIT_MASK & (1 << x.to_u64().unwrap())) != 0);
     }
 
     if x.is_even() {
-        return false;
+        return Some(false);
     }
 
     let r_a = &(x % PRIMES_A);
@@ -91,10 +123,10 @@ pub fn probably_prime(x: &BigUint, n: usize) -> bool {
         || (r_b % 47u32).is_zero()
         || (r_b % 53u32).is_zero()
     {
-        return false;
+        return Some(false);
     }
 
-    probably_prime_miller_rabin(x, n + 1, true) && probably_prime_lucas(x)
+    None
 }
 
 const NUMBER_OF_PRIMES: usize = 127;
@@ -181,15 +213,17 @@ pub fn next_prime(n: &BigUint) -> BigUint {
 /// Reports whether n passes reps rounds of the Miller-Rabin primality test, using pseudo-randomly chosen bases.
 /// If `force2` is true, one of the rounds is forced to use base 2.
 ///
+/// The bases are derived from the lowest limb of n; see `miller_rabin_with_rng`
+/// for bases that can't be predicted.
+///
 /// See Handbook of Applied Cryptography, p. 139, Algorithm 4.24.
 pub fn probably_prime_miller_rabin(n: &BigUint, reps: usize, force2: bool) -> bool {
-    // println!("miller-rabin: {}", n);
-    let nm1 = n - &*BIG_1;
-    // determine q, k such that nm1 = q << k
-    let k = nm1.trailing_zeros().unwrap() as usize;
-    let q = &nm1 >> k;
-    let nm3 = n - &*BIG_3;
+    miller_rabin_with_rng(n, reps, force2, &mut seeded_rng(n))
+}
 
+/// The random number generator for the bases of `probably_prime`, seeded
+/// with the lowest limb of n.
+fn seeded_rng(n: &BigUint) -> StdRng {
     let mut seed_vec = vec![0u8; 8];
     BigEndian::write_uint(
         seed_vec.as_mut_slice(),
@@ -198,33 +232,179 @@ pub fn probably_prime_miller_rabin(n: &BigUint, reps: usize, force2: bool) -> bo
     );
     let mut seed = [0u8; 32];
     seed[0..8].copy_from_slice(&seed_vec[..]);
-    let mut rng = StdRng::from_seed(seed);
+    StdRng::from_seed(seed)
+}
 
-    'nextrandom: for i in 0..reps {
+/// Reports whether n passes reps rounds of the Miller-Rabin primality test,
+/// using bases drawn uniformly from `[2, n - 2]` with `rng`.
+/// If `force2` is true, one of the rounds is forced to use base 2.
+///
+/// See Handbook of Applied Cryptography, p. 139, Algorithm 4.24.
+pub fn miller_rabin_with_rng<R: Rng + ?Sized>(
+    n: &BigUint,
+    reps: usize,
+    force2: bool,
+    rng: &mut R,
+) -> bool {
+    // println!("miller-rabin: {}", n);
+    let nm1 = n - &*BIG_1;
+    // determine q, k such that nm1 = q << k
+    let k = nm1.trailing_zeros().unwrap() as usize;
+    let q = &nm1 >> k;
+    let nm3 = n - &*BIG_3;
+
+    for i in 0..reps {
         let x = if i == reps - 1 && force2 {
             BIG_2.clone()
         } else {
             rng.gen_biguint_below(&nm3) + &*BIG_2
         };
 
-        let mut y = x.modpow(&q, n);
-        if y.is_one() || y == nm1 {
-            continue;
+        if !strong_probable_prime(n, &nm1, &q, k, &x) {
+            return false;
         }
+    }
 
-        for _ in 1..k {
-            y = y.modpow(&*BIG_2, n);
-            if y == nm1 {
-                break 'nextrandom;
-            }
-            if y.is_one() {
-                return false;
-            }
+    true
+}
+
+/// Reports whether n is a strong probable prime to the base x, where
+/// `n - 1 = q << k` with odd q.
+fn strong_probable_prime(n: &BigUint, nm1: &BigUint, q: &BigUint, k: usize, x: &BigUint) -> bool {
+    let mut y = x.modpow(q, n);
+    if y.is_one() || &y == nm1 {
+        return true;
+    }
+
+    for _ in 1..k {
+        y = y.modpow(&*BIG_2, n);
+        if &y == nm1 {
+            return true;
         }
+        if y.is_one() {
+            return false;
+        }
+    }
+    false
+}
+
+/// Reports whether n passes the Miller-Rabin test for each of the given bases.
+///
+/// The bases are reduced modulo n, and those divisible by n are skipped. With
+/// the bases from `deterministic_bases`, the result is exact.
+///
+/// Returns false if n is less than 2, or even and not 2.
+pub fn miller_rabin_with_bases(n: &BigUint, bases: &[BigUint]) -> bool {
+    if n < &*BIG_2 {
         return false;
     }
+    if n.is_even() {
+        return n == &*BIG_2;
+    }
 
-    true
+    let nm1 = n - &*BIG_1;
+    let k = nm1.trailing_zeros().unwrap();
+    let q = &nm1 >> k;
+
+    bases.iter().all(|a| {
+        let a = a % n;
+        a.is_zero() || strong_probable_prime(n, &nm1, &q, k, &a)
+    })
+}
+
+lazy_static! {
+    /// Bounds below which the first k primes are a set of Miller-Rabin bases
+    /// without strong pseudoprimes, as `(bound, k)`. See Jaeschke, "On strong
+    /// pseudoprimes to several bases", Mathematics of Computation 61(204),
+    /// 1993, and Sorenson and Webster, "Strong pseudoprimes to twelve prime
+    /// bases", Mathematics of Computation 86(304), 2017.
+    static ref DETERMINISTIC_BOUNDS: Vec<(BigUint, usize)> = [
+        ("2047", 1),
+        ("1373653", 2),
+        ("25326001", 3),
+        ("3215031751", 4),
+        ("2152302898747", 5),
+        ("3474749660383", 6),
+        ("341550071728321", 7),
+        ("3825123056546413051", 9),
+        ("318665857834031151167461", 12),
+        ("3317044064679887385961981", 13),
+    ]
+    .iter()
+    .map(|&(bound, k)| (BigUint::parse_bytes(bound.as_bytes(), 10).unwrap(), k))
+    .collect();
+}
+
+/// The primes up to 41, the bases used by `deterministic_bases`.
+const DETERMINISTIC_PRIMES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// Returns bases for which `miller_rabin_with_bases` decides exactly whether n
+/// is prime, the smallest set of leading primes known to suffice, or `None`
+/// if n is at least 3317044064679887385961981 (about 3.3·10²⁴), beyond which
+/// no such set is known.
+pub fn deterministic_bases(n: &BigUint) -> Option<Vec<BigUint>> {
+    let &(_, k) = DETERMINISTIC_BOUNDS.iter().find(|(bound, _)| n < bound)?;
+    Some(
+        DETERMINISTIC_PRIMES[..k]
+            .iter()
+            .map(|&p| BigUint::from(p))
+            .collect(),
+    )
+}
+
+/// How `probably_prime_with_policy` tests numbers that are too large for
+/// `deterministic_bases`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrimalityPolicy {
+    /// Miller-Rabin with random bases, with the number of rounds for the bit
+    /// length of the candidate from FIPS 186-5 Table B.1: 4 rounds from 1536
+    /// bits, 5 rounds from 1024 bits, and 44 rounds, the count for the
+    /// auxiliary primes of 4096-bit moduli, below that.
+    ///
+    /// These counts assume randomly generated candidates; numbers that an
+    /// adversary may have chosen need `BailliePsw` or more rounds.
+    Fips186,
+    /// The Baillie-PSW test only: Miller-Rabin with base 2 and the strong
+    /// Lucas test, without random bases. No composite is known to pass it.
+    BailliePsw,
+}
+
+impl PrimalityPolicy {
+    /// Returns the number of Miller-Rabin rounds with random bases for a
+    /// candidate of `bits` bits.
+    pub fn rounds(&self, bits: usize) -> usize {
+        match *self {
+            PrimalityPolicy::Fips186 if bits >= 1536 => 4,
+            PrimalityPolicy::Fips186 if bits >= 1024 => 5,
+            PrimalityPolicy::Fips186 => 44,
+            PrimalityPolicy::BailliePsw => 0,
+        }
+    }
+}
+
+/// Reports whether x is prime, testing it according to `policy`, with the
+/// random bases drawn from `rng`.
+///
+/// Numbers below 3.3·10²⁴ are decided exactly with `deterministic_bases`,
+/// whatever the policy.
+pub fn probably_prime_with_policy<R: Rng + ?Sized>(
+    x: &BigUint,
+    policy: PrimalityPolicy,
+    rng: &mut R,
+) -> bool {
+    if let Some(prime) = check_small_factors(x) {
+        return prime;
+    }
+    if let Some(bases) = deterministic_bases(x) {
+        return miller_rabin_with_bases(x, &bases);
+    }
+
+    match policy {
+        PrimalityPolicy::Fips186 => miller_rabin_with_rng(x, policy.rounds(x.bits()), false, rng),
+        PrimalityPolicy::BailliePsw => {
+            miller_rabin_with_bases(x, ::std::slice::from_ref(&*BIG_2)) && probably_prime_lucas(x)
+        }
+    }
 }
 
 /// Reports whether n passes the "almost extra strong" Lucas probable prime test,
@@ -443,6 +623,7 @@ mod tests {
     // use RandBigInt;
 
     use crate::biguint::ToBigUint;
+    use rand_xorshift::XorShiftRng;
 
     lazy_static! {
         static ref PRIMES: Vec<&'static str> = vec![
@@ -575,6 +756,109 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_probably_prime_with_rng() {
+        let mut rng = XorShiftRng::from_seed([1u8; 16]);
+        for (numbers, prime) in [(&*PRIMES, true), (&*COMPOSITES, false)].iter() {
+            for s in numbers.iter() {
+                let n = BigUint::parse_bytes(s.as_bytes(), 10).unwrap();
+                for &reps in [0, 1, 20].iter() {
+                    assert_eq!(probably_prime_with_rng(&n, reps, &mut rng), *prime, "{}", s);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_miller_rabin_with_rng() {
+        let mut rng = XorShiftRng::from_seed([1u8; 16]);
+
+        // A strong pseudoprime to all prime bases up to 200 fails for random bases.
+        let n = BigUint::parse_bytes(COMPOSITES[7].as_bytes(), 10).unwrap();
+        assert!(!miller_rabin_with_rng(&n, 20, false, &mut rng));
+
+        // 2047 = 23 * 89 is a strong pseudoprime to base 2 only.
+        let n = BigUint::from(2047u32);
+        assert!(miller_rabin_with_rng(&n, 1, true, &mut rng));
+        assert!(!miller_rabin_with_rng(&n, 20, false, &mut rng));
+
+        let p = BigUint::parse_bytes(PRIMES[10].as_bytes(), 10).unwrap();
+        assert!(miller_rabin_with_rng(&p, 20, false, &mut rng));
+    }
+
+    #[test]
+    fn test_miller_rabin_with_bases() {
+        let bases = |b: &[u32]| b.iter().map(|&b| BigUint::from(b)).collect::<Vec<_>>();
+
+        assert!(!miller_rabin_with_bases(&BigUint::zero(), &bases(&[2])));
+        assert!(!miller_rabin_with_bases(&BigUint::one(), &bases(&[2])));
+        assert!(miller_rabin_with_bases(&BigUint::from(2u32), &bases(&[2])));
+        assert!(miller_rabin_with_bases(
+            &BigUint::from(3u32),
+            &bases(&[2, 3])
+        ));
+        assert!(!miller_rabin_with_bases(&BigUint::from(4u32), &bases(&[3])));
+
+        // Strong pseudoprimes to the bases 2, 3, 5 and 7.
+        let n = BigUint::from(3_215_031_751u64);
+        assert!(miller_rabin_with_bases(&n, &bases(&[2, 3, 5, 7])));
+        assert!(!miller_rabin_with_bases(&n, &bases(&[2, 3, 5, 7, 11])));
+
+        let n = BigUint::parse_bytes(COMPOSITES[6].as_bytes(), 10).unwrap();
+        assert!(miller_rabin_with_bases(
+            &n,
+            &bases(&[2, 3, 5, 7, 11, 13, 17, 19, 23, 29])
+        ));
+    }
+
+    #[test]
+    fn test_deterministic_bases() {
+        for n in 2..100_000u32 {
+            let prime = (2..n).take_while(|d| d * d <= n).all(|d| n % d != 0);
+            let n = BigUint::from(n);
+            let bases = deterministic_bases(&n).unwrap();
+            assert_eq!(miller_rabin_with_bases(&n, &bases), prime, "{}", n);
+        }
+
+        // Each bound is a strong pseudoprime to the bases below it.
+        let mut previous = deterministic_bases(&BigUint::from(2046u32)).unwrap();
+        for (bound, _) in DETERMINISTIC_BOUNDS.iter() {
+            assert!(miller_rabin_with_bases(bound, &previous));
+            match deterministic_bases(bound) {
+                Some(bases) => {
+                    assert!(!miller_rabin_with_bases(bound, &bases));
+                    previous = bases;
+                }
+                None => assert_eq!(previous.len(), 13),
+            }
+        }
+    }
+
+    #[test]
+    fn test_probably_prime_with_policy() {
+        let mut rng = XorShiftRng::from_seed([1u8; 16]);
+        let policies = [PrimalityPolicy::Fips186, PrimalityPolicy::BailliePsw];
+        for (numbers, prime) in [(&*PRIMES, true), (&*COMPOSITES, false)].iter() {
+            for s in numbers.iter() {
+                let n = BigUint::parse_bytes(s.as_bytes(), 10).unwrap();
+                for &policy in policies.iter() {
+                    assert_eq!(
+                        probably_prime_with_policy(&n, policy, &mut rng),
+                        *prime,
+                        "{} {:?}",
+                        s,
+                        policy
+                    );
+                }
+            }
+        }
+
+        assert_eq!(PrimalityPolicy::Fips186.rounds(512), 44);
+        assert_eq!(PrimalityPolicy::Fips186.rounds(1024), 5);
+        assert_eq!(PrimalityPolicy::Fips186.rounds(2048), 4);
+        assert_eq!(PrimalityPolicy::BailliePsw.rounds(2048), 0);
+    }
+
     macro_rules! test_pseudo_primes {
         ($name:ident, $cond:expr, $want:expr) => {
             #[test]
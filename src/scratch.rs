@@ -0,0 +1,74 @@
+use algorithms::{mac3_scratch_len, mul_karatsuba_threshold};
+use big_digit::{self, BigDigit};
+use biguint::BigUint;
+use integer::Integer;
+use smallvec::SmallVec;
+
+/// A reusable workspace for the temporary values of `BigUint::mul_into`,
+/// `BigUint::div_rem_into` and `MontgomeryContext::modpow_into`.
+///
+/// The workspace grows to whatever the operations need and then keeps its
+/// storage, so a loop over operands of similar sizes only allocates in its
+/// first iterations. Use `with_capacity` to avoid even those.
+///
+/// # Examples
+///
+/// ```
+/// use num_bigint_dig::{BigUint, Scratch};
+///
+/// let a = BigUint::from(1u32) << 1000;
+/// let b = BigUint::from(12345u32);
+///
+/// let mut scratch = Scratch::with_capacity(2000);
+/// let (mut prod, mut q, mut r) = (BigUint::default(), BigUint::default(), BigUint::default());
+/// for _ in 0..10 {
+///     BigUint::mul_into(&mut prod, &a, &b, &mut scratch);
+///     BigUint::div_rem_into(&mut q, &mut r, &prod, &b, &mut scratch);
+///     assert_eq!(q, a);
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Scratch {
+    /// Raw digits for the multiplication and division kernels.
+    pub(crate) digits: Vec<BigDigit>,
+    /// Temporary numbers (and the table of powers) for `modpow_into`.
+    pub(crate) nums: Vec<BigUint>,
+}
+
+impl Scratch {
+    /// Creates an empty workspace.
+    pub fn new() -> Self {
+        Scratch::default()
+    }
+
+    /// Creates a workspace that is large enough for operands with up to `bits`
+    /// bits, so that even the first operations on them don't allocate (with
+    /// the current `Thresholds`).
+    pub fn with_capacity(bits: usize) -> Self {
+        let n = Integer::div_ceil(&bits, &big_digit::BITS);
+
+        // A division of a 2n digit number needs 3n + 1 digits.
+        let digits = ::std::cmp::max(mac3_scratch_len(n, mul_karatsuba_threshold()), 3 * n + 1);
+
+        // modpow_into uses four temporaries and at most 64 powers, of up to 2n digits each.
+        let nums = (0..4 + 64)
+            .map(|_| BigUint {
+                data: SmallVec::with_capacity(2 * n),
+            })
+            .collect();
+
+        Scratch {
+            digits: Vec::with_capacity(digits),
+            nums,
+        }
+    }
+
+    /// Returns the first `len` digits of the workspace, growing it if needed.
+    /// Their values are unspecified.
+    pub(crate) fn digits(&mut self, len: usize) -> &mut [BigDigit] {
+        if self.digits.len() < len {
+            self.digits.resize(len, 0);
+        }
+        &mut self.digits[..len]
+    }
+}
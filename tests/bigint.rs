@@ -5,7 +5,7 @@ extern crate num_traits;
 extern crate rand;
 
 use num_bigint::BigUint;
-use num_bigint::Sign::{Minus, NoSign, Plus};
+use num_bigint::Sign::{self, Minus, NoSign, Plus};
 use num_bigint::{BigInt, ToBigInt};
 
 use std::cmp::Ordering::{Equal, Greater, Less};
@@ -1219,3 +1219,68 @@ fn test_pow() {
     check!(u64);
     check!(usize);
 }
+
+fn pseudo_random(len: usize, seed: u32, sign: Sign) -> BigInt {
+    let digits = (0..len as u32)
+        .map(|i| (i ^ seed).wrapping_mul(0x9e37_79b9).rotate_left(i))
+        .collect();
+    BigInt::new(sign, digits)
+}
+
+#[test]
+fn test_add_mul_sub_mul() {
+    let sizes = [(3, 1, 1), (2, 20, 30), (500, 150, 200), (100, 300, 300)];
+    let signs = [Plus, Minus, NoSign];
+    for &(a_len, b_len, c_len) in &sizes {
+        for &a_sign in &signs {
+            for &b_sign in &signs {
+                for &c_sign in &signs {
+                    let a = pseudo_random(a_len, 1, a_sign);
+                    let b = pseudo_random(b_len, 2, b_sign);
+                    let c = pseudo_random(c_len, 3, c_sign);
+
+                    let mut x = a.clone();
+                    x.add_mul(&b, &c);
+                    assert_eq!(x, &a + &b * &c);
+                    x.sub_mul(&b, &c);
+                    assert_eq!(x, a);
+                    x.sub_mul(&b, &c);
+                    assert_eq!(x, &a - &b * &c);
+
+                    let mut y = &b * &c;
+                    y.sub_mul(&b, &c);
+                    assert!(y.is_zero() && y.sign() == NoSign);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_dot_product() {
+    let signs = [Plus, Minus, NoSign];
+    let b: Vec<_> = (0..30)
+        .map(|i| pseudo_random((i * 37) % 400, i as u32, signs[i % 3]))
+        .collect();
+    let c: Vec<_> = (0..30)
+        .map(|i| pseudo_random((i * 91) % 300, !i as u32, signs[i % 2]))
+        .collect();
+
+    let expected = b
+        .iter()
+        .zip(&c)
+        .fold(BigInt::zero(), |acc, (b, c)| acc + b * c);
+    assert_eq!(BigInt::dot_product(b.iter().zip(&c)), expected);
+    assert_eq!(
+        BigInt::dot_product(b.iter().zip(&c).map(|(b, c)| (c, b))),
+        expected
+    );
+    assert_eq!(
+        BigInt::dot_product(b.iter().zip(&c).take(0)),
+        BigInt::zero()
+    );
+
+    let neg: Vec<_> = b.iter().map(|b| -b).collect();
+    let pairs = b.iter().zip(&b).chain(neg.iter().zip(&b));
+    assert_eq!(BigInt::dot_product(pairs), BigInt::zero());
+}
@@ -1530,6 +1530,45 @@ fn test_all_str_radix() {
     }
 }
 
+#[test]
+fn test_big_str_radix() {
+    // Large enough for the divide and conquer conversions, with long runs of zero digits that
+    // have to be padded in between.
+    let ten = BigUint::from(10u32);
+    for &n in &[1000usize, 5000, 20000] {
+        let p: BigUint = ten.pow(n);
+        let s = p.to_string();
+        assert_eq!(s.len(), n + 1);
+        assert!(s.starts_with('1') && s[1..].bytes().all(|b| b == b'0'));
+        assert_eq!(BigUint::from_str(&s).unwrap(), p);
+
+        let s = (&p - 1u32).to_string();
+        assert!(s.len() == n && s.bytes().all(|b| b == b'9'));
+        assert_eq!(BigUint::from_str(&s).unwrap(), &p - 1u32);
+
+        let s = (&p * &p + 1u32).to_string();
+        assert_eq!(s, format!("1{}1", "0".repeat(2 * n - 1)));
+        assert_eq!(BigUint::from_str(&s).unwrap(), &p * &p + 1u32);
+    }
+
+    let n = BigUint::new(
+        (0..3000u32)
+            .map(|i| i.wrapping_mul(0x9e37_79b9).rotate_left(i))
+            .collect(),
+    );
+    for &radix in &[3, 10, 36] {
+        let s = n.to_str_radix(radix);
+        assert_eq!(BigUint::from_str_radix(&s, radix).unwrap(), n);
+    }
+    for &radix in &[3, 10, 190, 255] {
+        let digits = n.to_radix_be(radix);
+        assert_eq!(BigUint::from_radix_be(&digits, radix).unwrap(), n);
+        let mut padded = vec![0; 100];
+        padded.extend(digits);
+        assert_eq!(BigUint::from_radix_be(&padded, radix).unwrap(), n);
+    }
+}
+
 #[test]
 fn test_lower_hex() {
     let a = BigUint::parse_bytes(b"A", 16).unwrap();
@@ -1711,3 +1750,74 @@ fn test_pow() {
     #[cfg(has_i128)]
     check!(u128);
 }
+
+fn pseudo_random(len: usize, seed: u32) -> BigUint {
+    BigUint::new(
+        (0..len as u32)
+            .map(|i| (i ^ seed).wrapping_mul(0x9e37_79b9).rotate_left(i))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_add_mul_sub_mul() {
+    let sizes = [
+        (0, 3, 5),
+        (10, 1, 1),
+        (2, 20, 30),
+        (500, 150, 200),
+        (100, 300, 300),
+        (3000, 40, 2000),
+    ];
+    for &(a_len, b_len, c_len) in &sizes {
+        let a = pseudo_random(a_len, 1);
+        let b = pseudo_random(b_len, 2);
+        let c = pseudo_random(c_len, 3);
+
+        let mut x = a.clone();
+        x.add_mul(&b, &c);
+        assert_eq!(x, &a + &b * &c);
+        x.sub_mul(&b, &c);
+        assert_eq!(x, a);
+
+        x.add_mul(&b, &b);
+        assert_eq!(x, &a + &b * &b);
+        x.sub_mul(&b, &b);
+        assert_eq!(x, a);
+
+        x.add_mul(&b, &BigUint::zero());
+        x.sub_mul(&BigUint::zero(), &c);
+        assert_eq!(x, a);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_sub_mul_underflow() {
+    let mut a = BigUint::from(5u32);
+    a.sub_mul(&BigUint::from(2u32), &BigUint::from(3u32));
+}
+
+#[test]
+fn test_dot_product() {
+    let b: Vec<_> = (0..30)
+        .map(|i| pseudo_random((i * 37) % 400, i as u32))
+        .collect();
+    let c: Vec<_> = (0..30)
+        .map(|i| pseudo_random((i * 91) % 300, !i as u32))
+        .collect();
+
+    let expected = b
+        .iter()
+        .zip(&c)
+        .fold(BigUint::zero(), |acc, (b, c)| acc + b * c);
+    assert_eq!(BigUint::dot_product(b.iter().zip(&c)), expected);
+    assert_eq!(
+        BigUint::dot_product(b.iter().zip(&b)),
+        b.iter().map(|b| b * b).sum()
+    );
+    assert_eq!(
+        BigUint::dot_product(b.iter().zip(&c).take(0)),
+        BigUint::zero()
+    );
+}
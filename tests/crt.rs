@@ -0,0 +1,116 @@
+extern crate num_bigint_dig as num_bigint;
+extern crate num_integer;
+extern crate num_traits;
+
+use num_bigint::{crt, BigUint, CrtContext};
+use num_integer::Integer;
+use num_traits::{Num, One, Zero};
+
+fn pair(r: u32, m: u32) -> (BigUint, BigUint) {
+    (BigUint::from(r), BigUint::from(m))
+}
+
+#[test]
+fn test_crt_small() {
+    let moduli_sets: &[&[u32]] = &[
+        &[1],
+        &[7],
+        &[3, 5],
+        &[4, 6],
+        &[4, 6, 9],
+        &[2, 3, 4, 5, 6],
+        &[12, 18, 1, 10],
+    ];
+
+    for moduli in moduli_sets {
+        let lcm = moduli.iter().fold(1u32, |acc, m| acc.lcm(m));
+        let ctx = CrtContext::new(&moduli.iter().map(|&m| BigUint::from(m)).collect::<Vec<_>>());
+        assert_eq!(ctx.modulus(), &BigUint::from(lcm));
+
+        // Every combination of residues, including ones above the moduli.
+        let count: u32 = moduli.iter().map(|m| m + 1).product();
+        for mut c in 0..count {
+            let mut residues = Vec::new();
+            for &m in moduli.iter() {
+                residues.push(c % (m + 1));
+                c /= m + 1;
+            }
+
+            let expected = (0..lcm).find(|x| {
+                residues
+                    .iter()
+                    .zip(moduli.iter())
+                    .all(|(r, m)| x % m == r % m)
+            });
+            let congruences: Vec<_> = residues
+                .iter()
+                .zip(moduli.iter())
+                .map(|(&r, &m)| pair(r, m))
+                .collect();
+
+            assert_eq!(
+                crt(&congruences),
+                expected.map(|x| (BigUint::from(x), BigUint::from(lcm))),
+                "{:?}",
+                congruences
+            );
+            let residues: Vec<_> = residues.into_iter().map(BigUint::from).collect();
+            assert_eq!(ctx.reconstruct(&residues), expected.map(BigUint::from));
+        }
+    }
+}
+
+#[test]
+fn test_crt_empty() {
+    assert_eq!(crt(&[]), Some((BigUint::zero(), BigUint::one())));
+    assert_eq!(CrtContext::new(&[]).reconstruct(&[]), Some(BigUint::zero()));
+}
+
+#[test]
+fn test_crt_context_large() {
+    // Moduli of very different sizes with common factors.
+    let mut moduli = Vec::new();
+    let mut m = BigUint::from_str_radix("fedcba9876543210fedcba9876543211", 16).unwrap();
+    for i in 1..40u32 {
+        moduli.push(&m * BigUint::from(i * 6));
+        let sq = &m * &m + 12345u32;
+        let bits = 64 + 40 * (i as usize % 5);
+        m = &sq >> (sq.bits() - bits);
+    }
+    let ctx = CrtContext::new(&moduli);
+    let lcm = moduli.iter().fold(BigUint::one(), |acc, m| acc.lcm(m));
+    assert_eq!(ctx.modulus(), &lcm);
+    assert_eq!(ctx.moduli(), &moduli[..]);
+
+    let mut x = BigUint::from_str_radix("123456789abcdef0123456789abcdef", 16).unwrap();
+    for _ in 0..10 {
+        x = (&x * &x * &x + 7u32) % &lcm;
+        let residues: Vec<BigUint> = moduli.iter().map(|m| &x % m).collect();
+        assert_eq!(ctx.reconstruct(&residues), Some(x.clone()));
+
+        let congruences: Vec<_> = residues
+            .iter()
+            .cloned()
+            .zip(moduli.iter().cloned())
+            .collect();
+        assert_eq!(crt(&congruences), Some((x.clone(), lcm.clone())));
+
+        // All moduli are even, so this contradicts the other residues.
+        let mut residues = residues;
+        residues[5] += 1u32;
+        assert_eq!(ctx.reconstruct(&residues), None);
+    }
+}
+
+#[test]
+#[should_panic(expected = "divide by zero!")]
+fn test_crt_zero_modulus() {
+    let _ = crt(&[pair(1, 3), pair(0, 0)]);
+}
+
+#[test]
+#[should_panic(expected = "one residue per modulus is needed")]
+fn test_crt_context_wrong_length() {
+    let ctx = CrtContext::new(&[BigUint::from(3u32), BigUint::from(5u32)]);
+    let _ = ctx.reconstruct(&[BigUint::one()]);
+}
@@ -0,0 +1,259 @@
+#![cfg(feature = "prime")]
+
+extern crate num_bigint_dig as num_bigint;
+extern crate num_traits;
+extern crate rand;
+extern crate rand_xorshift;
+
+use num_bigint::factor::{
+    ecm, factor, pollard_pm1, pollard_rho_brent, primes_up_to, siqs, trial_division, EcmParams,
+    Factorization,
+};
+use num_bigint::prime::probably_prime;
+use num_bigint::BigUint;
+use num_traits::{Num, One, Pow};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+fn big(s: &str) -> BigUint {
+    BigUint::from_str_radix(s, 10).unwrap()
+}
+
+fn pairs(factors: &[(u64, u32)]) -> Vec<(BigUint, u32)> {
+    factors
+        .iter()
+        .map(|&(p, e)| (BigUint::from(p), e))
+        .collect()
+}
+
+#[test]
+fn test_primes_up_to() {
+    assert!(primes_up_to(0).is_empty());
+    assert!(primes_up_to(1).is_empty());
+    assert_eq!(primes_up_to(2), vec![2]);
+    assert_eq!(primes_up_to(30), vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+
+    // Around the end of the table.
+    for &limit in &[65520u64, 65521, 65535, 65536, 65537, 100_000] {
+        let primes = primes_up_to(limit);
+        let expected: Vec<u64> = (2..=limit)
+            .filter(|&n| (2..).take_while(|d| d * d <= n).all(|d| n % d != 0))
+            .collect();
+        assert_eq!(primes, expected, "{}", limit);
+    }
+    assert_eq!(primes_up_to(1_000_000).len(), 78498);
+}
+
+#[test]
+fn test_trial_division() {
+    let n = BigUint::from(2u32 * 2 * 3 * 97 * 101 * 101) * big("1000000007");
+    let (factors, rest) = trial_division(&n, 100);
+    assert_eq!(factors.factors(), &pairs(&[(2, 2), (3, 1), (97, 1)])[..]);
+    assert_eq!(rest, BigUint::from(101u32 * 101) * big("1000000007"));
+
+    let (factors, rest) = trial_division(&n, 1000);
+    assert_eq!(
+        factors.factors(),
+        &pairs(&[(2, 2), (3, 1), (97, 1), (101, 2)])[..]
+    );
+    assert_eq!(rest, big("1000000007"));
+
+    // A prime cofactor below the bound is found without dividing up to it.
+    let (factors, rest) = trial_division(&BigUint::from(2u32 * 65521), 65535);
+    assert_eq!(factors.factors(), &pairs(&[(2, 1), (65521, 1)])[..]);
+    assert!(rest.is_one());
+
+    let (factors, rest) = trial_division(&BigUint::one(), 100);
+    assert_eq!(factors, Factorization::new());
+    assert!(rest.is_one());
+}
+
+#[test]
+fn test_pollard_rho_brent() {
+    let p = big("1000003");
+    let q = big("4294967311");
+    let n = &p * &q;
+    let d = pollard_rho_brent(&n, 1, 1 << 20).unwrap();
+    assert!(d == p || d == q);
+
+    assert_eq!(pollard_rho_brent(&BigUint::from(3u32), 1, 1000), None);
+    assert_eq!(pollard_rho_brent(&big("4294967311"), 1, 1 << 20), None);
+    assert_eq!(
+        pollard_rho_brent(&BigUint::from(22u32), 1, 1000),
+        Some(BigUint::from(2u32))
+    );
+}
+
+#[test]
+fn test_pollard_pm1() {
+    // p - 1 = 2 * 3^2 * 5 * 7 * 11 * 13 * 4999 * 7927 * 100003 is smooth, q - 1 is not.
+    let p_minus_1 = BigUint::from(2u64 * 9 * 5 * 7 * 11 * 13 * 4999 * 7927) * 100_003u32;
+    let p = &p_minus_1 + 1u32;
+    assert!(probably_prime(&p, 20));
+    let q: BigUint = (BigUint::one() << 127) - 1u32;
+    let n = &p * &q;
+
+    // Stage 1 alone misses 100003, stage 2 finds it.
+    assert_eq!(pollard_pm1(&n, 10_000, 0), None);
+    assert_eq!(pollard_pm1(&n, 10_000, 100_002), None);
+    assert_eq!(pollard_pm1(&n, 10_000, 100_003), Some(p.clone()));
+    assert_eq!(pollard_pm1(&n, 100_003, 0), Some(p));
+
+    // Powers of the base are factored without running stage 1.
+    let three = BigUint::from(3u32);
+    assert_eq!(pollard_pm1(&9u32.into(), 100, 1000), Some(three.clone()));
+    assert_eq!(pollard_pm1(&27u32.into(), 100, 1000), Some(three));
+}
+
+#[test]
+fn test_ecm_params() {
+    assert_eq!(
+        EcmParams::for_digits(10),
+        EcmParams {
+            b1: 2_000,
+            b2: 200_000,
+            curves: 25
+        }
+    );
+    assert_eq!(EcmParams::for_digits(20), EcmParams::default());
+    assert_eq!(EcmParams::for_digits(21).b1, 50_000);
+    assert_eq!(EcmParams::for_digits(1000), EcmParams::for_digits(50));
+}
+
+#[test]
+fn test_ecm() {
+    let mut rng = XorShiftRng::from_seed([1u8; 16]);
+    let params = EcmParams::for_digits(15);
+    assert_eq!(ecm(&BigUint::from(3u32), &params, &mut rng), None);
+    assert_eq!(ecm(&BigUint::from(7u32), &params, &mut rng), None);
+    assert_eq!(
+        ecm(&BigUint::from(22u32), &params, &mut rng),
+        Some(BigUint::from(2u32))
+    );
+
+    let p = big("1000000000039");
+    let q: BigUint = (BigUint::one() << 127) - 1u32;
+    let n = &p * &q;
+    assert_eq!(ecm(&n, &params, &mut rng), Some(p.clone()));
+
+    // The same seed gives the same curves.
+    let mut rng = XorShiftRng::from_seed([2u8; 16]);
+    let params = EcmParams {
+        b1: 500,
+        b2: 0,
+        curves: 10,
+    };
+    let first = ecm(&n, &params, &mut rng);
+    let mut rng = XorShiftRng::from_seed([2u8; 16]);
+    assert_eq!(ecm(&n, &params, &mut rng), first);
+
+    // A prime has no factor to find.
+    let params = EcmParams {
+        b1: 1000,
+        b2: 10_000,
+        curves: 3,
+    };
+    assert_eq!(ecm(&q, &params, &mut rng), None);
+}
+
+#[test]
+fn test_siqs() {
+    assert_eq!(siqs(&BigUint::from(3u32)), None);
+    assert_eq!(siqs(&BigUint::from(22u32)), Some(BigUint::from(2u32)));
+    assert_eq!(siqs(&BigUint::from(21u32)), Some(BigUint::from(3u32)));
+
+    for &(p, q) in &[
+        ("400000000000063", "2000000000000021"),
+        ("30000000000000000041", "80000000000000000023"),
+    ] {
+        let (p, q) = (big(p), big(q));
+        let d = siqs(&(&p * &q)).unwrap();
+        assert!(d == p || d == q);
+    }
+
+    // Primes have no factor to find.
+    assert_eq!(siqs(&big("999999999989")), None);
+}
+
+#[test]
+fn test_factor() {
+    assert_eq!(factor(&BigUint::one()), Factorization::new());
+    assert_eq!(
+        factor(&BigUint::from(2u32)).factors(),
+        &pairs(&[(2, 1)])[..]
+    );
+
+    for n in 1u64..3000 {
+        let f = factor(&BigUint::from(n));
+        assert_eq!(f.value(), BigUint::from(n));
+        for &(ref p, e) in f.factors() {
+            assert!(probably_prime(p, 20) && e > 0);
+        }
+    }
+
+    // 30!
+    let n: BigUint = (1u32..=30).map(BigUint::from).product();
+    assert_eq!(
+        factor(&n).factors(),
+        &pairs(&[
+            (2, 26),
+            (3, 14),
+            (5, 7),
+            (7, 4),
+            (11, 2),
+            (13, 2),
+            (17, 1),
+            (19, 1),
+            (23, 1),
+            (29, 1)
+        ])[..]
+    );
+    assert_eq!(
+        factor(&n).to_string(),
+        "2^26 * 3^14 * 5^7 * 7^4 * 11^2 * 13^2 * 17 * 19 * 23 * 29"
+    );
+}
+
+#[test]
+fn test_factor_large() {
+    let m61 = (BigUint::one() << 61) - 1u32;
+    let m127 = (BigUint::one() << 127) - 1u32;
+
+    // Perfect powers of large primes.
+    let n = m61.clone().pow(3u32) * BigUint::from(3u32).pow(5u32) * &m127;
+    let f = factor(&n);
+    assert_eq!(
+        f.factors(),
+        &[
+            (BigUint::from(3u32), 5),
+            (m61.clone(), 3),
+            (m127.clone(), 1)
+        ][..]
+    );
+    assert_eq!(f.value(), n);
+
+    // Medium factors, found by the rho method and ECM.
+    let primes = [
+        big("65537"),
+        big("1000003"),
+        big("4294967311"),
+        big("1000000000039"),
+    ];
+    let n: BigUint = primes.iter().product::<BigUint>() * &primes[1] * &m61;
+    let f = factor(&n);
+    assert_eq!(f.value(), n);
+    assert_eq!(f.factors().len(), 5);
+    assert_eq!(f.factors()[1], (big("1000003"), 2));
+
+    // F_6 = 2^64 + 1 = 274177 * 67280421310721
+    let n = (BigUint::one() << 64) + 1u32;
+    assert_eq!(
+        factor(&n).factors(),
+        &[(big("274177"), 1), (big("67280421310721"), 1)][..]
+    );
+
+    // Two 48-bit factors, found by the quadratic sieve.
+    let p = big("281474976710597");
+    let q = big("281474976710591");
+    assert_eq!(factor(&(&p * &q)).factors(), &[(q, 1), (p, 1)][..]);
+}
@@ -57,9 +57,9 @@ static BIG_R: &'static str = "\
                               109c4735_6e7db425_7b5d74c7_0b709508";
 
 mod biguint {
-    use num_bigint::BigUint;
+    use num_bigint::{BarrettReducer, BigUint, FixedBaseExp, MontgomeryContext};
     use num_integer::Integer;
-    use num_traits::Num;
+    use num_traits::{Num, One, Pow, Zero};
 
     fn check_modpow<T: Into<BigUint>>(b: T, e: T, m: T, r: T) {
         let b: BigUint = b.into();
@@ -97,6 +97,299 @@ mod biguint {
         assert!(even_modpow < even_m);
         assert_eq!(even_modpow % m, r);
     }
+
+    // Square and multiply with a full division at every step.
+    fn naive_modpow(b: &BigUint, e: &BigUint, m: &BigUint) -> BigUint {
+        let mut acc = BigUint::one() % m;
+        for i in (0..e.bits()).rev() {
+            acc = &acc * &acc % m;
+            if ((e >> i) & BigUint::one()).is_one() {
+                acc = acc * b % m;
+            }
+        }
+        acc
+    }
+
+    #[test]
+    fn test_modpow_even() {
+        let b = BigUint::from_str_radix(super::BIG_B, 16).unwrap();
+        let e = BigUint::from_str_radix(super::BIG_E, 16).unwrap();
+        let m = BigUint::from_str_radix(super::BIG_M, 16).unwrap();
+
+        for &k in &[1, 2, 3, 31, 32, 33, 64, 65, 200, 2049] {
+            // pure powers of two
+            let pow2 = BigUint::one() << k;
+            assert_eq!(b.modpow(&e, &pow2), naive_modpow(&b, &e, &pow2));
+            assert_eq!((&b << 3).modpow(&e, &pow2), BigUint::zero());
+
+            let even_m = &m << k;
+            assert_eq!(b.modpow(&e, &even_m), naive_modpow(&b, &e, &even_m));
+
+            // tiny exponents
+            for y in 0u32..4 {
+                let y = BigUint::from(y);
+                assert_eq!(b.modpow(&y, &even_m), naive_modpow(&b, &y, &even_m));
+            }
+        }
+
+        for m in (2u32..100).step_by(2) {
+            let m = BigUint::from(m);
+            for x in 0u32..30 {
+                let x = BigUint::from(x);
+                for y in 0u32..30 {
+                    let y = BigUint::from(y);
+                    assert_eq!(x.modpow(&y, &m), naive_modpow(&x, &y, &m));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_modpow_windows() {
+        let b = BigUint::from_str_radix(super::BIG_B, 16).unwrap();
+        let e = BigUint::from_str_radix(super::BIG_E, 16).unwrap();
+        let m = BigUint::from_str_radix(super::BIG_M, 16).unwrap();
+
+        // exponents of every window size, with long runs of zero bits
+        let mut exps = vec![
+            BigUint::one(),
+            BigUint::from(2u32),
+            BigUint::from(3u32),
+            BigUint::from(65537u32),
+        ];
+        for &bits in &[24, 80, 240, 672, 1792, 2000] {
+            exps.push((BigUint::one() << (bits - 1)) + 1u32);
+            exps.push((BigUint::one() << bits) - 1u32);
+            exps.push(&e >> (e.bits() - bits));
+            exps.push((&e >> (e.bits() - bits / 2)) << (bits / 2));
+        }
+
+        for y in &exps {
+            assert_eq!(b.modpow(y, &m), naive_modpow(&b, y, &m));
+        }
+    }
+
+    #[test]
+    fn test_montgomery_context() {
+        let b = BigUint::from_str_radix(super::BIG_B, 16).unwrap();
+        let e = BigUint::from_str_radix(super::BIG_E, 16).unwrap();
+        let m = BigUint::from_str_radix(super::BIG_M, 16).unwrap();
+        let r = BigUint::from_str_radix(super::BIG_R, 16).unwrap();
+
+        let ctx = MontgomeryContext::new(&m);
+        assert_eq!(ctx.modulus(), &m);
+        assert_eq!(ctx.modpow(&b, &e), r);
+        // the context can be reused
+        assert_eq!(ctx.modpow(&b, &e), r);
+
+        let bm = ctx.to_montgomery(&b);
+        assert!(bm < m);
+        assert_eq!(ctx.from_montgomery(&bm), &b % &m);
+
+        let rm = ctx.to_montgomery(&r);
+        assert_eq!(ctx.from_montgomery(&ctx.mul(&bm, &rm)), &b * &r % &m);
+
+        let small = MontgomeryContext::new(&BigUint::from(19u32));
+        for x in 0u32..40 {
+            let x = BigUint::from(x);
+            for y in 0u32..40 {
+                assert_eq!(small.modpow(&x, &BigUint::from(y)), x.pow(y) % 19u32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_modpow() {
+        let b = BigUint::from_str_radix(super::BIG_B, 16).unwrap();
+        let e = BigUint::from_str_radix(super::BIG_E, 16).unwrap();
+        let m = BigUint::from_str_radix(super::BIG_M, 16).unwrap();
+        let r = BigUint::from_str_radix(super::BIG_R, 16).unwrap();
+
+        assert_eq!(BigUint::multi_modpow(&[(&b, &e)], &m), r);
+        assert_eq!(BigUint::multi_modpow(&[], &m), BigUint::one());
+
+        let bases = [
+            b.clone(),
+            &b >> 7,
+            &b * &e,
+            BigUint::zero(),
+            BigUint::from(2u32),
+        ];
+        let exps = [
+            e.clone(),
+            &e >> 1000,
+            BigUint::from(65537u32),
+            BigUint::zero(),
+            BigUint::one(),
+            (&e >> 30) << 30,
+        ];
+        for &m in &[&m, &(&m << 3), &(&m >> 1900)] {
+            for n in 0..bases.len() {
+                let pairs: Vec<_> = bases[..n].iter().zip(exps.iter().cycle().skip(n)).collect();
+                let expected = pairs
+                    .iter()
+                    .fold(BigUint::one() % m, |acc, &(x, y)| acc * x.modpow(y, m) % m);
+                assert_eq!(BigUint::multi_modpow(&pairs, m), expected);
+            }
+        }
+
+        // the same base and exponent several times
+        let pairs = [(&b, &e), (&b, &e), (&b, &e)];
+        assert_eq!(BigUint::multi_modpow(&pairs, &m), r.pow(3u32) % &m);
+
+        assert_eq!(
+            BigUint::multi_modpow(&[(&b, &e)], &BigUint::one()),
+            BigUint::zero()
+        );
+    }
+
+    #[test]
+    fn test_fixed_base_exp() {
+        let b = BigUint::from_str_radix(super::BIG_B, 16).unwrap();
+        let e = BigUint::from_str_radix(super::BIG_E, 16).unwrap();
+        let m = BigUint::from_str_radix(super::BIG_M, 16).unwrap();
+        let r = BigUint::from_str_radix(super::BIG_R, 16).unwrap();
+
+        let exp = FixedBaseExp::new(&b, &m, e.bits());
+        assert_eq!(exp.modulus(), &m);
+        assert_eq!(exp.max_exponent_bits(), e.bits());
+        assert_eq!(exp.modpow(&e), r);
+        assert_eq!(exp.modpow(&BigUint::zero()), BigUint::one());
+
+        let exps = [
+            BigUint::zero(),
+            BigUint::one(),
+            BigUint::from(65537u32),
+            &e >> 1500,
+            (&e >> 1000) << 500,
+            e.clone(),
+        ];
+        for &(max_bits, teeth, tables) in &[
+            (0, 1, 1),
+            (17, 3, 1),
+            (100, 1, 3),
+            (600, 4, 2),
+            (2048, 2, 5),
+            (2048, 7, 1),
+        ] {
+            for base in &[b.clone(), &b * &e, BigUint::zero(), BigUint::from(3u32)] {
+                let exp = FixedBaseExp::with_table_size(base, &m, max_bits, teeth, tables);
+                // including exponents longer than max_bits
+                for y in &exps {
+                    assert_eq!(exp.modpow(y), base.modpow(y, &m));
+                }
+            }
+        }
+
+        let small = FixedBaseExp::new(&BigUint::from(7u32), &BigUint::from(19u32), 6);
+        for y in 0u32..100 {
+            assert_eq!(
+                small.modpow(&BigUint::from(y)),
+                BigUint::from(7u32).pow(y) % 19u32
+            );
+        }
+
+        let one = FixedBaseExp::new(&b, &BigUint::one(), 10);
+        assert_eq!(one.modpow(&BigUint::zero()), BigUint::zero());
+        assert_eq!(one.modpow(&e), BigUint::zero());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fixed_base_exp_even() {
+        FixedBaseExp::new(&BigUint::from(3u32), &BigUint::from(10u32), 10);
+    }
+
+    #[test]
+    fn test_barrett_reducer() {
+        let b = BigUint::from_str_radix(super::BIG_B, 16).unwrap();
+        let e = BigUint::from_str_radix(super::BIG_E, 16).unwrap();
+        let m = BigUint::from_str_radix(super::BIG_M, 16).unwrap();
+        let r = BigUint::from_str_radix(super::BIG_R, 16).unwrap();
+
+        let ctx = BarrettReducer::new(&m);
+        assert_eq!(ctx.modulus(), &m);
+        assert_eq!(ctx.modpow(&b, &e), r);
+
+        let moduli = vec![
+            m.clone(),
+            &m << 1,
+            &m << 64,
+            &m >> 1900,
+            (&b >> 1000) << 7,
+            BigUint::one() << 100,
+            BigUint::from(1u32),
+            BigUint::from(2u32),
+            BigUint::from(0xffff_fffbu32),
+        ];
+        for m in &moduli {
+            let ctx = BarrettReducer::new(m);
+
+            // both shorter and much longer than the modulus
+            for x in &[
+                BigUint::zero(),
+                m.clone(),
+                m - 1u32,
+                &b >> 1500,
+                &b * &e,
+                (&b * &e * &b) << 3,
+            ] {
+                assert_eq!(ctx.reduce(x), x % m);
+                assert_eq!(ctx.sqr_mod(x), x * x % m);
+                assert_eq!(ctx.mul_mod(x, &e), x * &e % m);
+            }
+
+            for y in &[
+                BigUint::zero(),
+                BigUint::one(),
+                BigUint::from(2u32),
+                &e >> 1700,
+            ] {
+                assert_eq!(ctx.modpow(&b, y), naive_modpow(&b, y, m));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_barrett_reducer_zero() {
+        BarrettReducer::new(&BigUint::zero());
+    }
+
+    #[test]
+    fn test_modpow_ct() {
+        let b = BigUint::from_str_radix(super::BIG_B, 16).unwrap();
+        let e = BigUint::from_str_radix(super::BIG_E, 16).unwrap();
+        let m = BigUint::from_str_radix(super::BIG_M, 16).unwrap();
+        let r = BigUint::from_str_radix(super::BIG_R, 16).unwrap();
+
+        assert_eq!(b.modpow_ct(&e, &m), r);
+        assert_eq!(MontgomeryContext::new(&m).modpow_ct(&b, &e), r);
+
+        // unreduced bases and small moduli
+        for &m in &[1u32, 3, 19, 0xffff_fffb] {
+            let m = BigUint::from(m);
+            for x in 0u32..20 {
+                let x = BigUint::from(x) << 40;
+                for y in 0u32..20 {
+                    let y = BigUint::from(y);
+                    assert_eq!(x.modpow_ct(&y, &m), x.modpow(&y, &m));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_modpow_ct_even() {
+        BigUint::from(3u32).modpow_ct(&BigUint::from(3u32), &BigUint::from(10u32));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_montgomery_context_even() {
+        MontgomeryContext::new(&BigUint::from(10u32));
+    }
 }
 
 mod bigint {
@@ -0,0 +1,117 @@
+#![cfg(feature = "rand")]
+
+extern crate num_bigint_dig as num_bigint;
+extern crate num_traits;
+extern crate rand;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use num_bigint::{BigUint, MontgomeryContext, RandBigInt, Scratch};
+use num_traits::Zero;
+use rand::prelude::*;
+
+// Counts the allocations of the current thread, so that other tests don't interfere.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = Cell::new(0);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|a| a.get())
+}
+
+#[test]
+fn test_into_matches_operators() {
+    let seed = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    let mut rng = SmallRng::from_seed(seed);
+    let mut scratch = Scratch::new();
+    let (mut prod, mut q, mut r) = (BigUint::zero(), BigUint::zero(), BigUint::zero());
+
+    for _ in 0..200 {
+        let xbits = rng.gen_range(0, 1 << 14);
+        let ybits = rng.gen_range(0, 1 << 14);
+        let x = rng.gen_biguint(xbits);
+        let y = rng.gen_biguint(ybits);
+
+        BigUint::mul_into(&mut prod, &x, &y, &mut scratch);
+        assert_eq!(prod, &x * &y);
+
+        if !y.is_zero() {
+            BigUint::div_rem_into(&mut q, &mut r, &x, &y, &mut scratch);
+            assert_eq!((&q, &r), (&(&x / &y), &(&x % &y)));
+        }
+    }
+
+    for &bits in &[64, 200, 1024, 2048, 4096] {
+        let m = rng.gen_biguint(bits) | BigUint::from(1u32);
+        let ctx = MontgomeryContext::new(&m);
+        for &(xbits, ybits) in &[(bits, bits), (2 * bits, 17), (bits / 2, 1), (bits, 0)] {
+            let x = rng.gen_biguint(xbits);
+            let y = rng.gen_biguint(ybits);
+            ctx.modpow_into(&mut r, &x, &y, &mut scratch);
+            assert_eq!(r, x.modpow(&y, &m));
+        }
+    }
+}
+
+#[test]
+fn test_no_allocations() {
+    let seed = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    let mut rng = SmallRng::from_seed(seed);
+
+    let m = rng.gen_biguint(2048) | BigUint::from(1u32);
+    let ctx = MontgomeryContext::new(&m);
+    let operands: Vec<_> = (0..8)
+        .map(|i| {
+            let x = rng.gen_biguint(4096 >> (i % 4));
+            let y = rng.gen_biguint(2048 + 100 * i);
+            (x, y)
+        })
+        .collect();
+
+    let mut scratch = Scratch::with_capacity(4096);
+    let (mut prod, mut q, mut r) = (BigUint::zero(), BigUint::zero(), BigUint::zero());
+
+    let mut run = || {
+        for (x, y) in &operands {
+            BigUint::mul_into(&mut prod, x, y, &mut scratch);
+            BigUint::div_rem_into(&mut q, &mut r, &prod, x, &mut scratch);
+            assert!(r.is_zero());
+            BigUint::div_rem_into(&mut q, &mut r, x, y, &mut scratch);
+            ctx.modpow_into(&mut r, x, y, &mut scratch);
+            ctx.modpow_into(&mut prod, y, x, &mut scratch);
+        }
+    };
+
+    // warm up, so that the outputs have grown large enough
+    run();
+
+    let before = allocations();
+    run();
+    assert_eq!(allocations(), before);
+
+    // while the operators do allocate
+    let _ = &operands[0].0 * &operands[0].1;
+    assert!(allocations() > before);
+}
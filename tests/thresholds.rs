@@ -0,0 +1,75 @@
+#![cfg(feature = "rand")]
+
+extern crate num_bigint_dig as num_bigint;
+extern crate num_integer;
+extern crate num_traits;
+extern crate rand;
+
+use num_bigint::algorithms::Thresholds;
+use num_bigint::{BigUint, RandBigInt};
+use num_integer::Integer;
+use num_traits::{Num, Zero};
+use rand::prelude::*;
+
+fn results(rng: &mut SmallRng, count: usize) -> Vec<BigUint> {
+    let mut res = Vec::new();
+    for _ in 0..count {
+        let xbits = rng.gen_range(1, 1 << 10);
+        let ybits = rng.gen_range(1, 1 << 10);
+        let x = rng.gen_biguint(xbits);
+        let y = rng.gen_biguint(ybits);
+        if y.is_zero() {
+            continue;
+        }
+
+        let (q, r) = (&x * &x * &y).div_rem(&y);
+        res.push(&x * &y);
+        res.push(&x * &x);
+        res.push(q);
+        res.push(r);
+        res.push(x.gcd(&y));
+        res.push(BigUint::from_str_radix(&x.to_str_radix(10), 10).unwrap());
+        res.push(x.modpow(&y, &(&y | BigUint::from(1u32))));
+    }
+    res
+}
+
+// The only test in this file that may change the global thresholds, which all
+// tests in this binary share. `test_invalid_thresholds` is safe to run next to
+// it: `set` checks every value before storing any, so it panics with the
+// globals untouched.
+#[test]
+fn test_small_thresholds() {
+    let seed = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    let expected = results(&mut SmallRng::from_seed(seed), 8);
+
+    Thresholds {
+        mul_karatsuba: 2,
+        mul_toom3: 2,
+        mul_ntt: 64,
+        sqr_long: 0,
+        sqr_karatsuba: 2,
+        sqr_toom3: 2,
+        sqr_ntt: 64,
+        montgomery_sqr: 0,
+        div_burnikel_ziegler: 2,
+        gcd_half_gcd: 2,
+        extended_gcd_half_gcd: 2,
+        radix_conversion: 2,
+    }
+    .set();
+    let actual = results(&mut SmallRng::from_seed(seed), 8);
+    Thresholds::default().set();
+
+    assert!(expected == actual);
+}
+
+#[test]
+#[should_panic]
+fn test_invalid_thresholds() {
+    Thresholds {
+        div_burnikel_ziegler: 1,
+        ..Thresholds::default()
+    }
+    .set();
+}
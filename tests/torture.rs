@@ -1,11 +1,13 @@
 #![cfg(feature = "rand")]
 
 extern crate num_bigint_dig as num_bigint;
+extern crate num_integer;
 extern crate num_traits;
 extern crate rand;
 
-use num_bigint::RandBigInt;
-use num_traits::Zero;
+use num_bigint::{BigUint, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
 use rand::prelude::*;
 
 fn test_mul_divide_torture_count(count: usize) {
@@ -41,3 +43,41 @@ fn test_mul_divide_torture() {
 fn test_mul_divide_torture_long() {
     test_mul_divide_torture_count(1000000);
 }
+
+fn test_div_rem_torture_count(count: usize) {
+    let bits_max = 1 << 16;
+    let seed = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    let mut rng = SmallRng::from_seed(seed);
+
+    for i in 0..count {
+        // Large numbers of random sizes, so the recursive division kicks in:
+        let xbits = rng.gen_range(0, bits_max);
+        let ybits = rng.gen_range(1, xbits / 2 + 2);
+
+        let x = rng.gen_biguint(xbits);
+        let mut y = rng.gen_biguint(ybits);
+        if i % 4 == 0 {
+            // divisors with long runs of one bits give maximal quotient estimates
+            y = (BigUint::one() << ybits) - 1u32;
+        }
+
+        if y.is_zero() {
+            continue;
+        }
+
+        let (q, r) = x.div_rem(&y);
+        assert!(r < y);
+        assert_eq!(q * &y + r, x);
+    }
+}
+
+#[test]
+fn test_div_rem_torture() {
+    test_div_rem_torture_count(200);
+}
+
+#[test]
+#[ignore]
+fn test_div_rem_torture_long() {
+    test_div_rem_torture_count(100000);
+}